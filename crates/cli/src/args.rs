@@ -1,18 +1,65 @@
-use clap::{Command, Arg};
+use clap::{Arg, ArgMatches, Command, ErrorKind};
 use crossterm::tty::IsTty;
 use image::Rgb;
-use std::{env, io::stdout};
+use std::{env, io::stdout, path::Path};
 use term_image::{
-    ascii::AsciiOptions, block::BlockOptions, block::Charset, braille::BrailleOptions,
-    iterm::ItermOptions, kitty::KittyOptions,
+    ascii::{self, AsciiMode, AsciiOptions, Ramp},
+    block::Charset,
+    block::{BlockOptions, ColorFit, GlyphMatch},
+    braille::BrailleOptions,
+    grid::GridOptions,
+    iterm::ItermOptions,
+    kitty::KittyOptions,
+    Rgb as TermRgb,
 };
+use term_image_html::{HtmlOptions, Styling};
+use term_image_svg::SvgOptions;
 
 #[derive(Debug)]
 pub struct Options {
-    pub path: String,
+    pub paths: Vec<String>,
+    pub header: bool,
     pub truecolor: bool,
     pub renderer_options: RendererOption,
     pub still: bool,
+    pub speed: f64,
+    pub fps: Option<f64>,
+    /// Number of times to play animations, `Some(None)` for forever, `None` to use the file's count
+    pub loops: Option<Option<u32>>,
+    pub hold_last_frame: bool,
+    /// Play animations in place below the prompt
+    pub inline: bool,
+    /// Show every file as a thumbnail in a grid
+    pub grid: Option<GridOptions>,
+    /// Browse the files in a full screen viewer
+    pub interactive: bool,
+    /// Redraw the files whenever they change
+    pub watch: bool,
+    /// Multiplier for `fill_terminal` when the render size follows the terminal size, `None` when
+    /// the size was given or there is no terminal
+    pub follow_terminal: Option<(u16, u16)>,
+    /// Write to a file instead of the terminal
+    pub export: Option<Export>,
+}
+
+#[derive(Debug)]
+pub struct Export {
+    pub path: String,
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportFormat {
+    /// Text with color escapes
+    Ansi,
+    /// Text without colors
+    Text,
+    /// A page with each image in a `<pre>`
+    Html(HtmlOptions),
+    /// A single image drawn with shapes and text
+    Svg(SvgOptions),
+    /// An asciinema recording of an animation
+    Asciicast,
 }
 
 #[derive(Debug)]
@@ -26,7 +73,296 @@ pub enum RendererOption {
 
 /// Parse cli args into Options
 pub fn get_options() -> Options {
-    let matches = Command::new("Terminal Image Viewer")
+    let mut command = command();
+    let matches = command.get_matches_mut();
+
+    let char_set = if matches.is_present("no_slopes") {
+        Charset::NoSlopes
+    } else if matches.is_present("only_blocks") {
+        Charset::Blocks
+    } else if matches.is_present("only_halfs") {
+        Charset::Halfs
+    } else {
+        Charset::default()
+    };
+
+    let iterm = env::var("TERM_PROGRAM")
+        .map(|prog| prog == "iTerm.app")
+        .unwrap_or(false);
+    let kitty = env::var("TERM")
+        .map(|term| term.contains("kitty"))
+        .unwrap_or(false);
+
+    let html_options = HtmlOptions {
+        styling: if matches.is_present("html_classes") {
+            Styling::Classes
+        } else {
+            Styling::Inline
+        },
+    };
+    let svg_options = svg_options(&matches);
+    let export = matches.value_of("output").map(|path| {
+        let extension = Path::new(path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        Export {
+            path: path.into(),
+            format: match (matches.value_of("format"), extension.as_deref()) {
+                (Some("text"), _) => ExportFormat::Text,
+                (Some("html"), _) | (None, Some("html" | "htm")) => {
+                    ExportFormat::Html(html_options)
+                }
+                (Some("svg"), _) | (None, Some("svg")) => ExportFormat::Svg(svg_options.clone()),
+                (Some("cast"), _) | (None, Some("cast")) => ExportFormat::Asciicast,
+                _ => ExportFormat::Ansi,
+            },
+        }
+    });
+
+    // The format can come from the extension, so these can't be checked by clap
+    let format = export.as_ref().map(|export| &export.format);
+    let html = matches!(format, Some(ExportFormat::Html(_)));
+    if matches.is_present("html_classes") && !html {
+        command
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--html-classes can only be used with html output",
+            )
+            .exit();
+    }
+    let svg = matches!(format, Some(ExportFormat::Svg(_)));
+    let svg_args = [
+        ("svg_font", "--svg-font"),
+        ("svg_cell_size", "--svg-cell-size"),
+        ("svg_foreground", "--svg-fg"),
+        ("svg_background", "--svg-bg"),
+    ];
+    if let (Some((_, flag)), false) = (svg_args.iter().find(|(id, _)| matches.is_present(id)), svg)
+    {
+        command
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!("{} can only be used with svg output", flag),
+            )
+            .exit();
+    }
+
+    let maybe_kitty_renderer = export.is_none()
+        && matches
+            .value_of("renderer")
+            .map(|r| matches!(r, "t" | "terminal" | "k" | "kitty"))
+            .unwrap_or(true);
+
+    let maybe_iterm_renderer = export.is_none()
+        && matches
+            .value_of("renderer")
+            .map(|r| matches!(r, "t" | "terminal" | "i" | "iterm"))
+            .unwrap_or(true);
+
+    // multiply default or terminal size by this value to make kitty renderer defaults more reasonable
+    let (width_multiplier, height_multiplier) = if kitty && maybe_kitty_renderer {
+        (12, 24)
+    } else {
+        (1, 1)
+    };
+
+    let tty = stdout().is_tty();
+    let multiplier = (width_multiplier, height_multiplier);
+    let mut follow_terminal = None;
+    let optional_term_size = match (
+        matches.value_of_t::<u16>("width"),
+        matches.value_of_t::<u16>("height"),
+    ) {
+        (width, height) if width.is_ok() || height.is_ok() => (width.ok(), height.ok()),
+        (_, _) if iterm && maybe_iterm_renderer => (None, None),
+        (_, _) if !tty => (Some(80 * width_multiplier), Some(25 * height_multiplier)),
+        _ => {
+            // An inline animation can't move to a new size without leaving the old one behind
+            if !matches.is_present("inline") && export.is_none() {
+                follow_terminal = Some(multiplier);
+            }
+            fill_terminal(multiplier)
+                .map(|(w, h)| (Some(w), Some(h)))
+                .unwrap_or((Some(80 * width_multiplier), Some(25 * height_multiplier)))
+        }
+    };
+
+    let term_size = (
+        optional_term_size.0.unwrap_or(u16::MAX),
+        optional_term_size.1.unwrap_or(u16::MAX),
+    );
+
+    let tile_width = matches
+        .value_of_t::<u16>("tile_width")
+        .expect("validated by clap")
+        .max(1);
+    let grid = if matches.is_present("grid") {
+        let width = match matches.value_of_t::<u16>("width") {
+            Ok(width) => width,
+            Err(_) if tty => crossterm::terminal::size().map_or(80, |(w, _)| w),
+            Err(_) => 80,
+        };
+        Some(GridOptions::fit(
+            usize::from(width),
+            usize::from(tile_width),
+            (2, 1),
+        ))
+    } else {
+        None
+    };
+    let interactive = matches.is_present("interactive");
+    // Thumbnails are rendered into a box of cells that is about square
+    let term_size = if grid.is_some() {
+        (tile_width, (tile_width / 2).max(1))
+    } else {
+        term_size
+    };
+
+    let background_color = matches
+        .value_of("background_color")
+        .and_then(parse_rgb_triplet)
+        .unwrap_or_else(|| [0, 0, 0].into());
+
+    let block_options = BlockOptions {
+        char_set,
+        blend: !matches.is_present("no_blending"),
+        blend_threshold: matches
+            .value_of_t("blend_threshold")
+            .expect("validated by clap"),
+        background_color,
+        size: term_size,
+        color_fit: if matches.is_present("perceptual") {
+            ColorFit::Perceptual
+        } else {
+            ColorFit::Split
+        },
+        glyph_match: if matches.is_present("quality") {
+            GlyphMatch::ColorError
+        } else {
+            GlyphMatch::Bitmap
+        },
+    };
+
+    let renderer_options = match matches.value_of("renderer").unwrap_or("t") {
+        "block" | "b" => RendererOption::Block(block_options),
+        "dots" | "d" => RendererOption::Braille(BrailleOptions {
+            size: term_size,
+            background_color,
+            background: matches.is_present("dots_background"),
+        }),
+        "ascii" | "a" => RendererOption::Ascii(AsciiOptions {
+            size: term_size,
+            background_color,
+            mode: if matches.is_present("structural") {
+                AsciiMode::Structural(ascii::glyphs::ASCII[..].into())
+            } else {
+                AsciiMode::Brightness(
+                    matches
+                        .value_of("ascii_ramp")
+                        .map(|ramp| Ramp::new(ramp).expect("validated by clap"))
+                        .or_else(|| matches.value_of("ascii_preset").and_then(Ramp::preset))
+                        .unwrap_or_default(),
+                )
+            },
+            background: matches.is_present("ascii_background"),
+            invert: matches.is_present("invert"),
+            monochrome: matches.is_present("monochrome"),
+        }),
+        "kitty" | "k" | "iterm" | "i" if export.is_some() => RendererOption::Block(block_options),
+        "kitty" | "k" => RendererOption::Kitty(KittyOptions { size: term_size }),
+        "iterm" | "i" if grid.is_some() || interactive => RendererOption::Block(block_options),
+        "iterm" | "i" => RendererOption::Iterm(ItermOptions {
+            size: optional_term_size,
+        }),
+        "terminal" | "t" => {
+            if kitty && export.is_none() {
+                RendererOption::Kitty(KittyOptions { size: term_size })
+            } else if iterm && grid.is_none() && !interactive && export.is_none() {
+                RendererOption::Iterm(ItermOptions {
+                    size: optional_term_size,
+                })
+            } else {
+                RendererOption::Block(block_options)
+            }
+        }
+        _ => unreachable!(),
+    };
+    // The terminal renderer picks blocks from the environment, so this can't be checked by clap
+    let blocks = matches!(renderer_options, RendererOption::Block(_));
+    if matches.occurrences_of("blend_threshold") > 0 && !blocks {
+        command
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--blend-threshold can only be used with the block renderer",
+            )
+            .exit();
+    }
+
+    let truecolor = !matches.is_present("256_colors")
+        && env::var("COLORTERM")
+            .map(|c| c.eq_ignore_ascii_case("truecolor"))
+            .unwrap_or(false);
+
+    Options {
+        paths: matches
+            .values_of("file_name")
+            .expect("required by clap")
+            .flat_map(expand_glob)
+            .collect(),
+        header: matches.is_present("header"),
+        truecolor,
+        renderer_options,
+        still: matches.is_present("still")
+            || matches.is_present("watch")
+            || export
+                .as_ref()
+                .is_some_and(|export| export.format != ExportFormat::Asciicast),
+        speed: matches.value_of_t("speed").expect("validated by clap"),
+        fps: matches.value_of_t("fps").ok(),
+        loops: if matches.is_present("once") {
+            Some(Some(1))
+        } else {
+            matches
+                .value_of_t::<u32>("loop")
+                .ok()
+                .map(|loops| Some(loops).filter(|&loops| loops != 0))
+        },
+        hold_last_frame: matches.is_present("hold_last_frame"),
+        inline: matches.is_present("inline"),
+        grid,
+        interactive,
+        watch: matches.is_present("watch"),
+        follow_terminal,
+        export,
+    }
+}
+
+/// The svg options from the `--svg-*` arguments
+fn svg_options(matches: &ArgMatches) -> SvgOptions {
+    let defaults = SvgOptions::default();
+    SvgOptions {
+        font_family: matches
+            .value_of("svg_font")
+            .map_or(defaults.font_family, String::from),
+        cell_size: matches
+            .value_of("svg_cell_size")
+            .and_then(parse_cell_size)
+            .unwrap_or(defaults.cell_size),
+        foreground: matches
+            .value_of("svg_foreground")
+            .and_then(parse_rgb_triplet)
+            .map_or(defaults.foreground, term_rgb),
+        background: match matches.value_of("svg_background") {
+            Some("none") => None,
+            Some(v) => parse_rgb_triplet(v).map(term_rgb),
+            None => defaults.background,
+        },
+    }
+}
+
+/// The command line arguments
+fn command() -> Command<'static> {
+    Command::new("Terminal Image Viewer")
         .author("Noskcaj")
         .about("Shows images in your terminal")
         .arg(
@@ -76,6 +412,75 @@ pub fn get_options() -> Options {
                 .conflicts_with_all(&["all", "no_slopes", "only_blocks"])
                 .requires_ifs(&[("block", "renderer"), ("b", "renderer")]),
         )
+        .arg(
+            Arg::new("perceptual")
+                .long("perceptual")
+                .help("Fit block colors in a perceptual color space (slower)")
+                .requires_ifs(&[("block", "renderer"), ("b", "renderer")]),
+        )
+        .arg(
+            Arg::new("quality")
+                .long("quality")
+                .help("Choose block characters by color error instead of shape (slower)")
+                .requires_ifs(&[("block", "renderer"), ("b", "renderer")]),
+        )
+        .arg(
+            Arg::new("blend_threshold")
+                .long("blend-threshold")
+                .takes_value(true)
+                .default_value("10")
+                .help("Number of mismatched pixels (out of 32) before a blending character is used, ignored with --quality")
+                .validator(|v| v.parse::<u32>()),
+        )
+        .arg(
+            Arg::new("dots_background")
+                .long("dots-bg")
+                .help("Color the background of braille cells")
+                .requires_ifs(&[("dots", "renderer"), ("d", "renderer")]),
+        )
+        .arg(
+            Arg::new("structural")
+                .long("structural")
+                .help("Match the shape of each ascii character instead of only its brightness")
+                .requires_ifs(&[("ascii", "renderer"), ("a", "renderer")]),
+        )
+        .arg(
+            Arg::new("ascii_ramp")
+                .long("ascii-ramp")
+                .takes_value(true)
+                .conflicts_with("structural")
+                .requires_ifs(&[("ascii", "renderer"), ("a", "renderer")])
+                .help("Characters to use from darkest to brightest, spaced evenly")
+                .validator(validate_ramp),
+        )
+        .arg(
+            Arg::new("ascii_preset")
+                .long("ascii-preset")
+                .takes_value(true)
+                .possible_values(["standard", "detailed", "blocks", "digits"])
+                .conflicts_with_all(&["structural", "ascii_ramp"])
+                .requires_ifs(&[("ascii", "renderer"), ("a", "renderer")])
+                .help("Built in character ramp to use"),
+        )
+        .arg(
+            Arg::new("ascii_background")
+                .long("ascii-bg")
+                .help("Color the background of ascii cells")
+                .conflicts_with("monochrome")
+                .requires_ifs(&[("ascii", "renderer"), ("a", "renderer")]),
+        )
+        .arg(
+            Arg::new("invert")
+                .long("invert")
+                .help("Draw ascii characters where the image is dark, for light terminal backgrounds")
+                .requires_ifs(&[("ascii", "renderer"), ("a", "renderer")]),
+        )
+        .arg(
+            Arg::new("monochrome")
+                .long("mono")
+                .help("Output ascii characters without colors")
+                .requires_ifs(&[("ascii", "renderer"), ("a", "renderer")]),
+        )
         .arg(
             Arg::new("width")
                 .long("width")
@@ -96,6 +501,44 @@ pub fn get_options() -> Options {
                 .short('s')
                 .help("Don't animate images"),
         )
+        .arg(
+            Arg::new("speed")
+                .long("speed")
+                .takes_value(true)
+                .default_value("1")
+                .help("Animation speed multiplier, from 0.001 to 1000")
+                .validator(|v| validate_range(v, 0.001, 1000.)),
+        )
+        .arg(
+            Arg::new("fps")
+                .long("fps")
+                .takes_value(true)
+                .help("Maximum number of animation frames drawn per second, from 0.001 to 1000")
+                .validator(|v| validate_range(v, 0.001, 1000.)),
+        )
+        .arg(
+            Arg::new("loop")
+                .long("loop")
+                .takes_value(true)
+                .help("Number of times to play animations, 0 for forever (defaults to the file's loop count, or once for files that loop forever when showing several)")
+                .validator(|v| v.parse::<u32>()),
+        )
+        .arg(
+            Arg::new("once")
+                .long("once")
+                .conflicts_with("loop")
+                .help("Play animations once"),
+        )
+        .arg(
+            Arg::new("hold_last_frame")
+                .long("hold-last-frame")
+                .help("Leave the last frame of an animation on screen when it finishes"),
+        )
+        .arg(
+            Arg::new("inline")
+                .long("inline")
+                .help("Play animations below the prompt instead of clearing the screen, keeping the last frame"),
+        )
         .arg(
             Arg::new("background_color")
                 .long("bg")
@@ -109,141 +552,235 @@ pub fn get_options() -> Options {
                 .long("renderer")
                 .takes_value(true)
                 .default_value("terminal")
-                .possible_values(&[
+                .possible_values([
                     "block", "b", "dots", "d", "ascii", "a", "kitty", "k", "iterm", "i",
                     "terminal", "t",
                 ])
                 .help("Renderer to use"),
         )
+        .arg(
+            Arg::new("grid")
+                .long("grid")
+                .help("Show files as a grid of thumbnails with their names and sizes (iterm uses blocks instead)"),
+        )
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .conflicts_with("grid")
+                .help("Browse files in a full screen viewer with zoom and pan (iterm uses blocks instead)"),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .conflicts_with_all(&["grid", "interactive"])
+                .help("Redraw whenever a file changes, animations are shown as still images"),
+        )
+        .arg(
+            Arg::new("tile_width")
+                .long("tile-width")
+                .takes_value(true)
+                .default_value("24")
+                .help("Width of each thumbnail in cells")
+                .validator(|v| v.parse::<u16>()),
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .help("Print the file name above each image"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .takes_value(true)
+                .conflicts_with_all(&["grid", "interactive", "watch"])
+                .help("Write the rendered output to a file, animations are written as still images unless recorded as an asciicast (kitty and iterm use blocks instead)"),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .requires("output")
+                .possible_values(["ansi", "text", "html", "svg", "cast"])
+                .help("Format of the output file, text has no colors [default: from the file extension, otherwise ansi]"),
+        )
+        .arg(
+            Arg::new("html_classes")
+                .long("html-classes")
+                .requires("output")
+                .help("Color html output with classes and a stylesheet instead of inline styles"),
+        )
+        .arg(
+            Arg::new("svg_font")
+                .long("svg-font")
+                .takes_value(true)
+                .requires("output")
+                .help("CSS font family of svg output [default: monospace]"),
+        )
+        .arg(
+            Arg::new("svg_cell_size")
+                .long("svg-cell-size")
+                .takes_value(true)
+                .requires("output")
+                .help("Comma seperated width and height of each cell in svg output, the font size is the height [default: 8,16]")
+                .validator(validate_cell_size),
+        )
+        .arg(
+            Arg::new("svg_foreground")
+                .long("svg-fg")
+                .takes_value(true)
+                .requires("output")
+                .help("Comma seperated rgb value of characters without a color in svg output [default: 255,255,255]")
+                .validator(validate_rgb_triplet),
+        )
+        .arg(
+            Arg::new("svg_background")
+                .long("svg-bg")
+                .takes_value(true)
+                .requires("output")
+                .help("Comma seperated rgb value behind svg output, or none for transparent [default: 0,0,0]")
+                .validator(|v| match v {
+                    "none" => Ok(()),
+                    v => validate_rgb_triplet(v).map(drop),
+                }),
+        )
         .arg(
             Arg::new("file_name")
                 .required(true)
-                .help("Input file name, - for stdin"),
+                .multiple_values(true)
+                .help("Input file names or glob patterns, - for stdin"),
         )
-        .get_matches();
+}
 
-    let char_set = if matches.is_present("no_slopes") {
-        Charset::NoSlopes
-    } else if matches.is_present("only_blocks") {
-        Charset::Blocks
-    } else if matches.is_present("only_halfs") {
-        Charset::Halfs
+/// The render size that fills the terminal with a small margin, scaled by `multiplier`
+pub fn fill_terminal(multiplier: (u16, u16)) -> Option<(u16, u16)> {
+    crossterm::terminal::size().ok().map(|(w, h)| {
+        (
+            w.saturating_sub(4) * multiplier.0,
+            h.saturating_sub(8) * multiplier.1,
+        )
+    })
+}
+
+/// Expand a glob pattern that isn't an existing path, for shells that don't expand globs
+///
+/// Patterns without any matches are kept as-is so they are reported as missing files.
+fn expand_glob(pattern: &str) -> Vec<String> {
+    if pattern == "-" || Path::new(pattern).exists() {
+        return vec![pattern.into()];
+    }
+    let paths = glob::glob(pattern)
+        .map(|paths| {
+            paths
+                .flatten()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if paths.is_empty() {
+        vec![pattern.into()]
     } else {
-        Charset::default()
-    };
+        paths
+    }
+}
 
-    let iterm = env::var("TERM_PROGRAM")
-        .map(|prog| prog == "iTerm.app")
-        .unwrap_or(false);
-    let kitty = env::var("TERM")
-        .map(|term| term.contains("kitty"))
-        .unwrap_or(false);
+fn parse_rgb_triplet(v: &str) -> Option<Rgb<u8>> {
+    let mut parts = v.split(',').flat_map(str::parse);
 
-    let maybe_kitty_renderer = matches
-        .value_of("renderer")
-        .map(|r| matches!(r, "t" | "terminal" | "k" | "kitty"))
-        .unwrap_or(true);
+    Some([parts.next()?, parts.next()?, parts.next()?].into())
+}
 
-    let maybe_iterm_renderer = matches
-        .value_of("renderer")
-        .map(|r| matches!(r, "t" | "terminal" | "i" | "iterm"))
-        .unwrap_or(true);
+fn validate_rgb_triplet(v: &str) -> Result<Rgb<u8>, String> {
+    parse_rgb_triplet(v).ok_or_else(|| "color not in R,G,B format".into())
+}
 
-    // multiply default or terminal size by this value to make kitty renderer defaults more reasonable
-    let (width_multiplier, height_multiplier) = if kitty && maybe_kitty_renderer {
-        (12, 24)
-    } else {
-        (1, 1)
-    };
+fn term_rgb(Rgb([r, g, b]): Rgb<u8>) -> TermRgb {
+    TermRgb((r, g, b))
+}
 
-    let tty = stdout().is_tty();
-    let optional_term_size = match (
-        matches.value_of_t::<u16>("width"),
-        matches.value_of_t::<u16>("height"),
-    ) {
-        (width, height) if width.is_ok() || height.is_ok() => (width.ok(), height.ok()),
-        (_, _) if iterm && maybe_iterm_renderer => (None, None),
-        (_, _) if !tty => (Some(80 * width_multiplier), Some(25 * height_multiplier)),
-        _ => crossterm::terminal::size()
-            .map(|(w, h)| {
-                (
-                    Some((w - 4) * width_multiplier),
-                    Some((h - 8) * height_multiplier),
-                )
-            })
-            .unwrap_or((Some(80 * width_multiplier), Some(25 * height_multiplier))),
-    };
+fn parse_cell_size(v: &str) -> Option<(f64, f64)> {
+    let (width, height) = v.split_once(',')?;
+    let size: (f64, f64) = (width.parse().ok()?, height.parse().ok()?);
+    Some(size).filter(|&(width, height)| width > 0. && height > 0.)
+}
 
-    let term_size = (
-        optional_term_size.0.unwrap_or(u16::MAX),
-        optional_term_size.1.unwrap_or(u16::MAX),
-    );
+fn validate_cell_size(v: &str) -> Result<(), String> {
+    parse_cell_size(v)
+        .map(drop)
+        .ok_or_else(|| "cell size not in W,H format with positive numbers".into())
+}
 
-    let background_color = matches
-        .value_of("background_color")
-        .and_then(parse_rgb_triplet)
-        .unwrap_or_else(|| [0, 0, 0].into());
+fn validate_ramp(v: &str) -> Result<(), String> {
+    Ramp::new(v)
+        .map(drop)
+        .ok_or_else(|| "ascii ramp must contain at least two characters".into())
+}
 
-    let renderer_options = match matches.value_of("renderer").unwrap_or("t") {
-        "block" | "b" => RendererOption::Block(BlockOptions {
-            char_set,
-            blend: !matches.is_present("no_blending"),
-            background_color,
-            size: term_size,
-        }),
-        "dots" | "d" => RendererOption::Braille(BrailleOptions {
-            size: term_size,
-            background_color,
-        }),
-        "ascii" | "a" => RendererOption::Ascii(AsciiOptions {
-            size: term_size,
-            background_color,
-        }),
-        "kitty" | "k" => RendererOption::Kitty(KittyOptions { size: term_size }),
-        "iterm" | "i" => RendererOption::Iterm(ItermOptions {
-            size: optional_term_size,
-        }),
-        "terminal" | "t" => {
-            if kitty {
-                RendererOption::Kitty(KittyOptions { size: term_size })
-            } else if iterm {
-                RendererOption::Iterm(ItermOptions {
-                    size: optional_term_size,
-                })
-            } else {
-                RendererOption::Block(BlockOptions {
-                    char_set,
-                    blend: !matches.is_present("no_blending"),
-                    background_color,
-                    size: term_size,
-                })
-            }
-        }
-        _ => unreachable!(),
-    };
+fn validate_range(v: &str, min: f64, max: f64) -> Result<(), String> {
+    match v.parse::<f64>() {
+        Ok(v) if (min..=max).contains(&v) => Ok(()),
+        _ => Err(format!("must be a number from {} to {}", min, max)),
+    }
+}
 
-    let truecolor = !matches.is_present("256_colors")
-        && env::var("COLORTERM")
-            .map(|c| c.to_ascii_lowercase() == "truecolor")
-            .unwrap_or(false);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use term_image::{ascii::Cell, CellBuffer};
+    use term_image_svg::Svg;
 
-    Options {
-        path: matches
-            .value_of("file_name")
-            .expect("required by clap")
-            .into(),
-        truecolor,
-        renderer_options,
-        still: matches.is_present("still"),
+    fn parse(args: &[&str]) -> clap::Result<ArgMatches> {
+        let args = ["term-image", "-o", "out.svg"]
+            .iter()
+            .chain(args)
+            .chain(&["in.png"]);
+        command().try_get_matches_from(args)
     }
-}
 
-fn parse_rgb_triplet(v: &str) -> Option<Rgb<u8>> {
-    let mut parts = v.split(',').map(str::parse).flatten();
+    fn write_svg(args: &[&str]) -> String {
+        let options = svg_options(&parse(args).unwrap());
+        let buffer = CellBuffer::new(
+            1,
+            vec![Cell {
+                ch: 'a',
+                fg: None,
+                bg: None,
+            }],
+        );
+        let mut out = Vec::new();
+        Svg::write(&options, &buffer, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
 
-    Some([parts.next()?, parts.next()?, parts.next()?].into())
-}
+    #[test]
+    fn svg_arguments_show_up_in_the_output() {
+        let args = [
+            "--svg-font",
+            "serif",
+            "--svg-cell-size",
+            "5,9.5",
+            "--svg-fg",
+            "1,2,3",
+            "--svg-bg",
+            "4,5,6",
+        ];
+        let svg = write_svg(&args);
+        assert!(svg.contains(r##"<rect width="5" height="9.5" fill="#040506"/>"##));
+        assert!(svg.contains(r##"<g font-family="serif" font-size="9.5" fill="#010203">"##));
 
-fn validate_rgb_triplet(v: &str) -> Result<Rgb<u8>, String> {
-    parse_rgb_triplet(v).ok_or_else(|| "background color not in R,G,B format".into())
+        assert!(!write_svg(&["--svg-bg", "none"]).contains("<rect"));
+        assert_eq!(svg_options(&parse(&[]).unwrap()), SvgOptions::default());
+    }
+
+    #[test]
+    fn invalid_svg_arguments_are_rejected() {
+        for args in [
+            ["--svg-cell-size", "0,16"],
+            ["--svg-cell-size", "8"],
+            ["--svg-fg", "1,2"],
+            ["--svg-bg", "transparent"],
+        ] {
+            assert!(parse(&args).is_err(), "{:?}", args);
+        }
+    }
 }
@@ -0,0 +1,203 @@
+use crate::{
+    args::{Options, RendererOption},
+    img_src::ImageSource,
+};
+use crossterm::{cursor, queue};
+use image::{DynamicImage, GenericImageView, Rgb};
+use std::{io::Write, path::Path};
+use term_image::{
+    ascii::{self, Ascii},
+    block::{self, Block},
+    braille::{self, Braille},
+    fit_cells,
+    grid::{compose, GridOptions, Tile},
+    kitty::Kitty,
+    CellBuffer, Rgb as TermRgb,
+};
+use term_image_crossterm::{write_buffer, TermWriter};
+
+/// Show every file as a captioned thumbnail, returning the files that couldn't be shown
+pub fn show_grid<'a>(
+    options: &'a Options,
+    grid: &GridOptions,
+    stdout: &mut impl Write,
+) -> Vec<&'a String> {
+    let mut failed = Vec::new();
+    let result = {
+        // Images are loaded as they are rendered so they are never all in memory at once
+        let mut images = options.paths.iter().filter_map(|path| {
+            match ImageSource::new(path.clone()).and_then(|src| src.img()) {
+                Ok(img) => Some((path, img)),
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    failed.push(path);
+                    None
+                }
+            }
+        });
+        match &options.renderer_options {
+            RendererOption::Block(block_options) => {
+                let fg = caption_color(block_options.background_color);
+                let [r, g, b] = block_options.background_color.0;
+                let bg = TermRgb((r, g, b));
+                write_grid(
+                    grid,
+                    &mut images,
+                    |img| Block::buffer(block_options, img),
+                    |ch| block::Cell { ch, fg, bg },
+                    options.truecolor,
+                    stdout,
+                )
+            }
+            RendererOption::Ascii(ascii_options) => write_grid(
+                grid,
+                &mut images,
+                |img| Ascii::buffer(ascii_options, img),
+                |ch| ascii::Cell {
+                    ch,
+                    fg: None,
+                    bg: None,
+                },
+                options.truecolor,
+                stdout,
+            ),
+            RendererOption::Braille(braille_options) => {
+                let fg = caption_color(braille_options.background_color);
+                write_grid(
+                    grid,
+                    &mut images,
+                    |img| Braille::buffer(braille_options, img),
+                    |ch| braille::Cell { ch, fg, bg: None },
+                    options.truecolor,
+                    stdout,
+                )
+            }
+            RendererOption::Kitty(_) => write_kitty_grid(grid, &mut images, stdout),
+            RendererOption::Iterm(_) => {
+                unreachable!("grids are drawn with blocks instead of iterm")
+            }
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error occurred printing grid: {}", e);
+    }
+
+    failed
+}
+
+/// Render every image and print them as one composed buffer
+fn write_grid<'a, C: Clone + TermWriter>(
+    grid: &GridOptions,
+    images: impl Iterator<Item = (&'a String, DynamicImage)>,
+    render: impl Fn(&DynamicImage) -> CellBuffer<C>,
+    text: impl Fn(char) -> C,
+    truecolor: bool,
+    stdout: &mut impl Write,
+) -> crossterm::Result<()> {
+    let tiles = images
+        .map(|(path, img)| Tile {
+            buffer: render(&img),
+            caption: caption(path, &img),
+        })
+        .collect::<Vec<_>>();
+
+    write_buffer(&compose(grid, &tiles, text), truecolor, stdout)?;
+    stdout.flush()
+}
+
+/// Place kitty images next to each other one row at a time, with the captions printed below
+fn write_kitty_grid<'a>(
+    grid: &GridOptions,
+    mut images: impl Iterator<Item = (&'a String, DynamicImage)>,
+    stdout: &mut impl Write,
+) -> crossterm::Result<()> {
+    let tile_size = (grid.tile_width as u16, (grid.tile_width as u16 / 2).max(1));
+
+    for i in 0.. {
+        let row = images
+            .by_ref()
+            .take(grid.columns.max(1))
+            .collect::<Vec<_>>();
+        if row.is_empty() {
+            break;
+        }
+        if i > 0 {
+            write!(stdout, "{}", "\n".repeat(grid.gap.1))?;
+        }
+
+        let cells = row
+            .iter()
+            .map(|(_, img)| fit_cells(img.dimensions(), tile_size))
+            .collect::<Vec<_>>();
+        let height = cells.iter().map(|&(_, rows)| rows).max().unwrap_or(1) as u16;
+
+        // Scroll first so the images have room, then draw them from the top of the row
+        // A row of empty images has no height, and moving up 0 lines moves up 1
+        write!(stdout, "{}", "\n".repeat(height.into()))?;
+        if height > 0 {
+            queue!(stdout, cursor::MoveToPreviousLine(height))?;
+        }
+        for (column, ((_, img), &(columns, rows))) in row.iter().zip(&cells).enumerate() {
+            // Kitty would show an image placed over 0 cells at its full size
+            if columns == 0 || rows == 0 {
+                continue;
+            }
+            let x =
+                column * (grid.tile_width + grid.gap.0) + (grid.tile_width - columns as usize) / 2;
+            move_to_column(x, stdout)?;
+            Kitty::img_cells(img, (columns, rows), stdout)?;
+        }
+        writeln!(stdout)?;
+        write!(
+            stdout,
+            "{}",
+            "\n".repeat(usize::from(height).saturating_sub(1))
+        )?;
+
+        let captions = row
+            .iter()
+            .map(|(path, img)| caption(path, img))
+            .collect::<Vec<_>>();
+        let caption_height = captions.iter().map(Vec::len).max().unwrap_or(0);
+        for line in 0..caption_height {
+            for (column, caption) in captions.iter().enumerate() {
+                let text = caption
+                    .get(line)
+                    .map(|text| text.chars().take(grid.tile_width).collect::<String>())
+                    .unwrap_or_default();
+                let x = column * (grid.tile_width + grid.gap.0)
+                    + (grid.tile_width - text.chars().count()) / 2;
+                move_to_column(x, stdout)?;
+                write!(stdout, "{}", text)?;
+            }
+            writeln!(stdout)?;
+        }
+    }
+    stdout.flush()
+}
+
+/// Move to a 0-based column, crossterm's `MoveToColumn` is 1-based
+fn move_to_column(x: usize, stdout: &mut impl Write) -> crossterm::Result<()> {
+    queue!(stdout, cursor::MoveToColumn(x as u16 + 1))
+}
+
+/// The file name and size of an image
+fn caption(path: &str, img: &DynamicImage) -> Vec<String> {
+    let name = match path {
+        "-" => "stdin".into(),
+        path => Path::new(path)
+            .file_name()
+            .map_or_else(|| path.into(), |name| name.to_string_lossy().into_owned()),
+    };
+    vec![name, format!("{}x{}", img.width(), img.height())]
+}
+
+/// White or black, whichever is easier to read on `background`
+fn caption_color(background: Rgb<u8>) -> TermRgb {
+    let [r, g, b] = background.0;
+    if u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114 > 128_000 {
+        TermRgb((0, 0, 0))
+    } else {
+        TermRgb((255, 255, 255))
+    }
+}
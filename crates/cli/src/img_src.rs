@@ -1,88 +1,405 @@
-use image::{codecs::gif::GifDecoder, AnimationDecoder, DynamicImage};
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    AnimationDecoder, DynamicImage, ImageFormat,
+};
 use std::{
+    convert::TryInto,
     fs::File,
-    io::{stdin, Read},
-    result::Result::{Err, Ok},
+    io::{stdin, Cursor, Read},
+    result::Result::Ok,
+    sync::Arc,
 };
 
-pub struct ImageSource(String);
+/// Animated image formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Animation {
+    Gif,
+    Apng,
+    WebP,
+}
+
+/// An input image, read into memory once so stdin can be decoded more than once
+#[derive(Clone)]
+pub struct ImageSource {
+    path: String,
+    data: Arc<[u8]>,
+    format: Option<ImageFormat>,
+    animation: Option<Animation>,
+}
 
 impl ImageSource {
-    pub fn new(filename: String) -> Self {
-        Self(filename)
+    pub fn new(filename: String) -> Result<Self, String> {
+        let data = read(&filename)?;
+        // Formats without magic bytes (like tga) can only be recognized by their extension
+        let format = image::guess_format(&data)
+            .ok()
+            .or_else(|| ImageFormat::from_path(&filename).ok());
+        let animation = match format {
+            Some(ImageFormat::Gif) if has_multiple_frames(&data) => Some(Animation::Gif),
+            Some(ImageFormat::Png) if is_apng(&data) => Some(Animation::Apng),
+            Some(ImageFormat::WebP) if is_animated_webp(&data) => Some(Animation::WebP),
+            _ => None,
+        };
+
+        Ok(Self {
+            path: filename,
+            data: data.into(),
+            format,
+            animation,
+        })
     }
 
     pub fn has_path(&self) -> bool {
-        self.0 != "-" && std::path::PathBuf::from(&self.0).exists()
+        self.path != "-" && std::path::PathBuf::from(&self.path).exists()
     }
 
     pub fn has_frames(&self) -> bool {
-        self.0.ends_with(".gif")
+        self.animation.is_some()
     }
 
     pub fn path(&self) -> &str {
-        &self.0
-    }
-
-    pub fn img(&self) -> DynamicImage {
-        if self.0 == "-" {
-            let mut buf = Vec::new();
-            if let Err(e) = stdin().read_to_end(&mut buf) {
-                eprintln!("Error occurred reading file: {}", e);
-                std::process::exit(1)
-            };
-
-            match image::load_from_memory(&buf) {
-                Ok(img) => img,
-                Err(e) => {
-                    eprintln!("Error occurred parsing file: {}", e);
-                    std::process::exit(1)
-                }
+        &self.path
+    }
+
+    pub fn img(&self) -> Result<DynamicImage, String> {
+        let format = self
+            .format
+            .ok_or("Unable to parse file: unrecognized image format")?;
+        image::load_from_memory_with_format(&self.data, format)
+            .map_err(|e| format!("Unable to parse file: {}", e))
+    }
+
+    pub fn raw(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Number of times an animation should be played, `None` for forever
+    ///
+    /// For gifs the NETSCAPE2.0 repeat count is the number of repeats after the first play like in
+    /// browsers, and a gif without the extension plays once.
+    pub fn loops(&self) -> Option<u32> {
+        match self.animation? {
+            Animation::Gif => match netscape_repeats(&self.data) {
+                Some(0) => None,
+                Some(repeats) => Some(u32::from(repeats) + 1),
+                None => Some(1),
+            },
+            Animation::Apng => apng_plays(&self.data).filter(|&plays| plays != 0),
+            Animation::WebP => webp_loops(&self.data)
+                .filter(|&loops| loops != 0)
+                .map(u32::from),
+        }
+    }
+
+    pub fn frames(&self) -> Result<image::Frames<'static>, String> {
+        let reader = Cursor::new(Arc::clone(&self.data));
+        let frames = match self.animation {
+            Some(Animation::Apng) => {
+                PngDecoder::new(reader).map(|decoder| decoder.apng().into_frames())
             }
-        } else {
-            match image::open(&self.0) {
-                Ok(img) => img,
-                Err(e) => {
-                    eprintln!("Error occurred opening image: {}", e);
-                    std::process::exit(1)
+            Some(Animation::WebP) => WebPDecoder::new(reader).map(|decoder| decoder.into_frames()),
+            _ => GifDecoder::new(reader).map(|decoder| decoder.into_frames()),
+        };
+        frames.map_err(|e| format!("Unable to decode animation: {}", e))
+    }
+}
+
+/// Read all of a file, or stdin for `-`
+fn read(filename: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    if filename == "-" {
+        stdin()
+            .read_to_end(&mut out)
+            .map_err(|e| format!("Unable to read file: {}", e))?;
+    } else {
+        File::open(filename)
+            .map_err(|e| format!("Unable to open file: {}", e))?
+            .read_to_end(&mut out)
+            .map_err(|e| format!("Unable to read file: {}", e))?;
+    }
+    Ok(out)
+}
+
+/// Whether a gif has more than one frame, single frame gifs are shown as still images
+fn has_multiple_frames(data: &[u8]) -> bool {
+    GifDecoder::new(Cursor::new(data))
+        .map(|decoder| decoder.into_frames().take(2).count() > 1)
+        .unwrap_or(false)
+}
+
+/// Whether `data` is a png with an animation control chunk
+fn is_apng(data: &[u8]) -> bool {
+    PngDecoder::new(Cursor::new(data)).is_ok_and(|decoder| decoder.is_apng())
+}
+
+/// Whether `data` is an extended webp with the animation flag set
+fn is_animated_webp(data: &[u8]) -> bool {
+    data.len() > 20 && &data[0..4] == b"RIFF" && &data[8..16] == b"WEBPVP8X" && data[20] & 0x02 != 0
+}
+
+/// Find the repeat count in a gif's NETSCAPE2.0 application extension
+///
+/// The blocks before the first image are walked, since the extension has to come before it.
+fn netscape_repeats(data: &[u8]) -> Option<u16> {
+    // Skip the header, the logical screen descriptor, and the global color table
+    let packed = *data.get(10)?;
+    let color_table = if packed & 0x80 != 0 {
+        3 << ((packed & 7) + 1)
+    } else {
+        0
+    };
+    let mut pos = 13 + color_table;
+    loop {
+        match data.get(pos..pos + 2)? {
+            [0x21, 0xff] => {
+                let id = gif_sub_block(data, pos + 2)?;
+                if id == b"NETSCAPE2.0" || id == b"ANIMEXTS1.0" {
+                    return match gif_sub_block(data, pos + 3 + id.len())? {
+                        [1, lo, hi] => Some(u16::from_le_bytes([*lo, *hi])),
+                        _ => None,
+                    };
                 }
+                pos = skip_gif_sub_blocks(data, pos + 2)?;
             }
+            [0x21, _] => pos = skip_gif_sub_blocks(data, pos + 2)?,
+            // An image descriptor or the trailer
+            _ => return None,
         }
     }
+}
 
-    pub fn raw(&self) -> Vec<u8> {
-        let mut out = Vec::new();
-        if self.0 == "-" {
-            if let Err(e) = stdin().read_to_end(&mut out) {
-                eprintln!("Error occurred reading file: {}", e);
-                std::process::exit(1);
+/// The data of the gif sub-block at `pos`
+fn gif_sub_block(data: &[u8], pos: usize) -> Option<&[u8]> {
+    let len = usize::from(*data.get(pos)?);
+    data.get(pos + 1..pos + 1 + len)
+}
+
+/// The position after the sub-blocks starting at `pos` and their terminator
+fn skip_gif_sub_blocks(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        match usize::from(*data.get(pos)?) {
+            0 => return Some(pos + 1),
+            len => pos += len + 1,
+        }
+    }
+}
+
+/// Find the number of plays in an apng's acTL chunk, which comes before the image data
+fn apng_plays(data: &[u8]) -> Option<u32> {
+    // Skip the signature
+    let mut pos = 8;
+    loop {
+        let len = u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        match data.get(pos + 4..pos + 8)? {
+            b"acTL" => {
+                let plays = data.get(pos + 12..pos + 16)?;
+                return Some(u32::from_be_bytes(plays.try_into().ok()?));
             }
-        } else {
-            match File::open(&self.0) {
-                Ok(mut file) => {
-                    if let Err(e) = file.read_to_end(&mut out) {
-                        eprintln!("Error occurred reading file: {}", e);
-                        std::process::exit(1);
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Error occurred opening file: {}", e);
-                    std::process::exit(1);
-                }
+            b"IDAT" | b"IEND" => return None,
+            // Length, type, data, and CRC
+            _ => pos += 12 + len,
+        }
+    }
+}
+
+/// Find the loop count in a webp's ANIM chunk
+fn webp_loops(data: &[u8]) -> Option<u16> {
+    // Skip the RIFF header and the WEBP form type
+    let mut pos = 12;
+    loop {
+        let len = u32::from_le_bytes(data.get(pos + 4..pos + 8)?.try_into().ok()?) as usize;
+        if data.get(pos..pos + 4)? == b"ANIM" {
+            let loops = data.get(pos + 12..pos + 14)?;
+            return Some(u16::from_le_bytes(loops.try_into().ok()?));
+        }
+        // Chunks are padded to an even length
+        pos += 8 + len + len % 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A gif header and logical screen descriptor, with a 2 color global color table
+    fn gif_header() -> Vec<u8> {
+        let mut gif = b"GIF89a\x01\x00\x01\x00\x80\x00\x00".to_vec();
+        gif.extend([0; 6]);
+        gif
+    }
+
+    fn netscape(repeats: u16) -> Vec<u8> {
+        let mut block = b"\x21\xff\x0bNETSCAPE2.0\x03\x01".to_vec();
+        block.extend(repeats.to_le_bytes());
+        block.push(0);
+        block
+    }
+
+    /// An image descriptor, its image data, and the trailer
+    fn gif_image() -> Vec<u8> {
+        b"\x2c\x00\x00\x00\x00\x01\x00\x01\x00\x00\x02\x02\x44\x01\x00\x3b".to_vec()
+    }
+
+    fn gif(blocks: &[&[u8]]) -> Vec<u8> {
+        let mut gif = gif_header();
+        for block in blocks {
+            gif.extend(*block);
+        }
+        gif.extend(gif_image());
+        gif
+    }
+
+    fn gif_source(data: Vec<u8>) -> ImageSource {
+        ImageSource {
+            path: "-".into(),
+            data: data.into(),
+            format: Some(ImageFormat::Gif),
+            animation: Some(Animation::Gif),
+        }
+    }
+
+    #[test]
+    fn gif_without_netscape_plays_once() {
+        let data = gif(&[]);
+        assert_eq!(netscape_repeats(&data), None);
+        assert_eq!(gif_source(data).loops(), Some(1));
+    }
+
+    #[test]
+    fn gif_netscape_repeats_after_the_first_play() {
+        let data = gif(&[&netscape(0)]);
+        assert_eq!(netscape_repeats(&data), Some(0));
+        assert_eq!(gif_source(data).loops(), None);
+
+        let data = gif(&[&netscape(4)]);
+        assert_eq!(netscape_repeats(&data), Some(4));
+        assert_eq!(gif_source(data).loops(), Some(5));
+    }
+
+    #[test]
+    fn gif_extensions_before_netscape_are_skipped() {
+        // A comment split over two sub-blocks, a graphic control extension, and another
+        // application extension
+        let comment = b"\x21\xfe\x03abc\x02de\x00";
+        let control = b"\x21\xf9\x04\x00\x0a\x00\x00\x00";
+        let xmp = b"\x21\xff\x0bXMP DataXMP\x02<x\x00";
+        let data = gif(&[comment, control, xmp, &netscape(2)]);
+        assert_eq!(netscape_repeats(&data), Some(2));
+    }
+
+    #[test]
+    fn gif_netscape_after_the_first_image_is_ignored() {
+        let mut data = gif(&[]);
+        data.pop();
+        data.extend(netscape(2));
+        data.push(0x3b);
+        assert_eq!(netscape_repeats(&data), None);
+    }
+
+    #[test]
+    fn truncated_gifs_do_not_panic() {
+        let data = gif(&[b"\x21\xfe\x03abc\x00", &netscape(3)]);
+        for len in 0..data.len() {
+            let repeats = netscape_repeats(&data[..len]);
+            assert!(repeats.is_none() || repeats == Some(3), "{} bytes", len);
+        }
+    }
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for byte in bytes {
+            crc ^= u32::from(*byte);
+            for _ in 0..8 {
+                crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
             }
         }
-        out
+        !crc
+    }
+
+    fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend(kind);
+        chunk.extend(data);
+        chunk.extend(crc32(&chunk[4..]).to_be_bytes());
+        chunk
     }
 
-    pub fn frames(&self) -> image::Frames {
-        // To get frames, we need a gif::Decoder, which
-        // takes a type that is Read
-        if self.0 == "-" {
-            GifDecoder::new(stdin()).ok().unwrap().into_frames()
-        } else {
-            let f = File::open(&self.0).expect("File not found");
+    /// A 1x1 RGB png, with an acTL chunk for `plays` if set
+    fn png(plays: Option<u32>) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend(png_chunk(b"IHDR", b"\0\0\0\x01\0\0\0\x01\x08\x02\0\0\0"));
+        png.extend(png_chunk(b"tEXt", b"Comment\0before acTL"));
+        if let Some(plays) = plays {
+            let mut actl = 1u32.to_be_bytes().to_vec();
+            actl.extend(plays.to_be_bytes());
+            png.extend(png_chunk(b"acTL", &actl));
+        }
+        png.extend(png_chunk(
+            b"IDAT",
+            b"\x78\x9c\x63\x60\x60\x60\x00\x00\x00\x04\x00\x01",
+        ));
+        png.extend(png_chunk(b"IEND", b""));
+        png
+    }
+
+    fn riff_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = kind.to_vec();
+        chunk.extend((data.len() as u32).to_le_bytes());
+        chunk.extend(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
 
-            GifDecoder::new(f).ok().unwrap().into_frames()
+    /// An extended webp with the animation flag, with an odd sized chunk before `ANIM`
+    fn webp(loops: u16) -> Vec<u8> {
+        let mut chunks = riff_chunk(b"VP8X", b"\x12\0\0\0\0\0\0\0\0\0");
+        chunks.extend(riff_chunk(b"ICCP", b"odd"));
+        let mut anim = vec![0; 4];
+        anim.extend(loops.to_le_bytes());
+        chunks.extend(riff_chunk(b"ANIM", &anim));
+        chunks.extend(riff_chunk(b"ANMF", &[0; 16]));
+
+        let mut webp = b"RIFF".to_vec();
+        webp.extend((chunks.len() as u32 + 4).to_le_bytes());
+        webp.extend(b"WEBP");
+        webp.extend(chunks);
+        webp
+    }
+
+    #[test]
+    fn apng_plays_come_from_actl() {
+        assert!(is_apng(&png(Some(0))));
+        assert_eq!(apng_plays(&png(Some(0))), Some(0));
+        assert_eq!(apng_plays(&png(Some(3))), Some(3));
+    }
+
+    #[test]
+    fn plain_png_is_not_animated() {
+        assert!(!is_apng(&png(None)));
+        assert_eq!(apng_plays(&png(None)), None);
+    }
+
+    #[test]
+    fn webp_loops_come_from_anim_after_padded_chunks() {
+        assert!(is_animated_webp(&webp(0)));
+        assert_eq!(webp_loops(&webp(0)), Some(0));
+        assert_eq!(webp_loops(&webp(7)), Some(7));
+    }
+
+    #[test]
+    fn truncated_pngs_and_webps_do_not_panic() {
+        for data in [png(Some(2)), webp(2)] {
+            for len in 0..data.len() {
+                let data = &data[..len];
+                is_apng(data);
+                is_animated_webp(data);
+                assert!(matches!(apng_plays(data), None | Some(2)), "{} bytes", len);
+                assert!(matches!(webp_loops(data), None | Some(2)), "{} bytes", len);
+            }
         }
+        assert!(!is_animated_webp(b"RIFF\x04\0\0\0WEBP"));
+        assert_eq!(webp_loops(b"RIFF\x04\0"), None);
     }
 }
@@ -1,169 +1,449 @@
-use crate::args::RendererOption;
-use crossterm::{cursor, queue, style, terminal};
-use image::Delay;
+use crate::args::{Export, ExportFormat, Options, RendererOption};
+use image::{Delay, Frame};
 use img_src::ImageSource;
-use itertools::IntoChunks;
 use std::{
-    io::{stdout, Write},
+    fs::File,
+    io::{stdout, BufWriter, Write},
+    iter,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
-    time::Duration,
 };
-use term_image::{ascii::Ascii, block::Block, braille::Braille, iterm::Iterm, kitty::Kitty};
-use term_image_crossterm::TermWriter;
+use term_image::{
+    ascii::{Ascii, AsciiOptions},
+    block::{Block, BlockOptions},
+    braille::{Braille, BrailleOptions},
+    export,
+    iterm::Iterm,
+    kitty::Kitty,
+    render_frames_batched, CellBuffer,
+};
+use term_image_crossterm::{
+    asciicast::{Asciicast, CastOptions},
+    guard::{GuardOptions, TerminalGuard},
+    player::{Player, PlayerOptions, Position},
+    write_buffer, TermWriter,
+};
+use term_image_html::{Html, Stylesheet, Styling};
+use term_image_svg::Svg;
 
 mod args;
+mod grid;
 mod img_src;
+mod viewer;
+mod watch;
+
+/// Number of animation frames rendered together
+const RENDER_BATCH: usize = 4;
 
 fn main() {
     let options = args::get_options();
-    let animated = !options.still;
 
     let stdout = stdout();
     let mut stdout = stdout.lock();
 
-    let src = ImageSource::new(options.path);
+    let failed = match &options.grid {
+        _ if options.interactive => viewer::run(&options, &mut stdout),
+        _ if options.watch => watch::watch(&options, &mut stdout),
+        Some(grid) => grid::show_grid(&options, grid, &mut stdout),
+        None => match &options.export {
+            Some(export) => export_files(&options, export),
+            None => show_files(&options, &mut None, &mut stdout),
+        },
+    };
+
+    if !failed.is_empty() {
+        eprintln!(
+            "Failed to show {} of {} files:",
+            failed.len(),
+            options.paths.len()
+        );
+        for path in failed {
+            eprintln!("    {}", path);
+        }
+        std::process::exit(1);
+    }
+}
+
+/// Write each file to the export path instead of the terminal, returning the files that couldn't
+/// be written
+fn export_files<'a>(options: &'a Options, export: &Export) -> Vec<&'a String> {
+    let single = match export.format {
+        ExportFormat::Svg(_) => Some("An SVG file"),
+        ExportFormat::Asciicast => Some("An asciicast"),
+        _ => None,
+    };
+    if let (Some(single), true) = (single, options.paths.len() > 1) {
+        eprintln!("{} can only show one image", single);
+        std::process::exit(1);
+    }
+
+    let file = match File::create(&export.path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Unable to create {}: {}", export.path, e);
+            std::process::exit(1);
+        }
+    };
+    let mut out = BufWriter::new(file);
+    let (failed, written) = match &export.format {
+        // The stylesheet goes in the head, so it is only known after every image is rendered
+        ExportFormat::Html(html_options) => {
+            let title = options.paths.first().map_or("term-image", String::as_str);
+            let mut stylesheet = match html_options.styling {
+                Styling::Inline => None,
+                Styling::Classes => Some(Stylesheet::default()),
+            };
+            let mut body = Vec::new();
+            let failed = show_files(options, &mut stylesheet, &mut body);
+            let written = Html::write_document_start(title, stylesheet.as_ref(), &mut out)
+                .and_then(|_| out.write_all(&body))
+                .and_then(|_| Html::write_document_end(&mut out));
+            (failed, written)
+        }
+        _ => (show_files(options, &mut None, &mut out), Ok(())),
+    };
+    if let Err(e) = written.and_then(|_| out.flush()) {
+        eprintln!("Unable to write {}: {}", export.path, e);
+        std::process::exit(1);
+    }
+    failed
+}
+
+/// Show each file in turn, returning the files that couldn't be shown
+///
+/// Interrupting an animation moves on to the next file.
+///
+/// The classes used by html images are added to `stylesheet` if it is set.
+fn show_files<'a>(
+    options: &'a Options,
+    stylesheet: &mut Option<Stylesheet>,
+    stdout: &mut impl Write,
+) -> Vec<&'a String> {
+    let mut signals = None;
+    let mut failed = Vec::new();
+    for path in &options.paths {
+        let header = Some(path.as_str()).filter(|_| options.header);
+        let shown = ImageSource::new(path.clone())
+            .and_then(|src| show(&src, header, options, &mut signals, stylesheet, stdout));
+        if let Err(e) = shown {
+            eprintln!("{}: {}", path, e);
+            failed.push(path);
+        }
+
+        if let Some(signals) = &signals {
+            // Quitting stops the remaining files too
+            if signals.quit.load(Ordering::Relaxed) {
+                break;
+            }
+            signals.interrupted.store(false, Ordering::Relaxed);
+        }
+    }
+    failed
+}
+
+/// Write the name of a file above it, unless it is exported as an SVG or asciicast
+fn write_header(options: &Options, path: &str, stdout: &mut impl Write) -> std::io::Result<()> {
+    match options.export.as_ref().map(|export| &export.format) {
+        Some(ExportFormat::Html(_)) => {
+            writeln!(stdout, "<p>{}</p>", term_image_html::escape(path))?
+        }
+        Some(ExportFormat::Svg(_) | ExportFormat::Asciicast) => return Ok(()),
+        _ => writeln!(stdout, "{}", path)?,
+    }
+    stdout.flush()
+}
 
-    match options.renderer_options {
+/// Show a single image or animation, with `header` above it
+///
+/// The classes used by an html image are added to `stylesheet` if it is set.
+fn show(
+    src: &ImageSource,
+    header: Option<&str>,
+    options: &Options,
+    signals: &mut Option<Signals>,
+    stylesheet: &mut Option<Stylesheet>,
+    stdout: &mut impl Write,
+) -> Result<(), String> {
+    let animated = !options.still && src.has_frames();
+    // The player clears the screen, so it draws the header itself
+    let played = animated
+        && options.export.is_none()
+        && !options.inline
+        && !matches!(options.renderer_options, RendererOption::Kitty(_));
+    if let (Some(header), false) = (header, played) {
+        let _ = write_header(options, header, stdout);
+    }
+
+    let player_options = PlayerOptions {
+        truecolor: options.truecolor,
+        speed: options.speed,
+        max_fps: options.fps,
+        // A forever-looping file would never move on to the files after it
+        loops: options.loops.unwrap_or_else(|| match src.loops() {
+            None if options.paths.len() > 1 => Some(1),
+            loops => loops,
+        }),
+        hold_last_frame: options.hold_last_frame,
+        inline: options.inline,
+        header: header.map(String::from),
+        ..PlayerOptions::default()
+    };
+
+    match &options.renderer_options {
         RendererOption::Block(block_options) => {
-            if src.has_frames() && animated {
+            if animated {
+                src.frames()?;
                 write_animated(
-                    Block::animated(&block_options, src.frames()),
-                    options.truecolor,
-                );
+                    |size| {
+                        let src = src.clone();
+                        let block_options = BlockOptions {
+                            size,
+                            ..*block_options
+                        };
+                        move || {
+                            render_frames_batched(frames(&src), RENDER_BATCH, move |img| {
+                                Block::buffer(&block_options, img)
+                            })
+                        }
+                    },
+                    block_options.size,
+                    options,
+                    &player_options,
+                    signals,
+                    stdout,
+                )
             } else {
                 write_still(
-                    Block::img(&block_options, &src.img()).into_iter(),
-                    options.truecolor,
-                );
+                    &Block::buffer(block_options, &src.img()?),
+                    options,
+                    stylesheet,
+                    stdout,
+                )
             }
         }
         RendererOption::Ascii(ascii_options) => {
-            if src.has_frames() && animated {
+            if animated {
+                src.frames()?;
                 write_animated(
-                    Ascii::animated(&ascii_options, src.frames()),
-                    options.truecolor,
-                );
+                    |size| {
+                        let src = src.clone();
+                        let ascii_options = AsciiOptions {
+                            size,
+                            ..ascii_options.clone()
+                        };
+                        move || {
+                            let ascii_options = ascii_options.clone();
+                            render_frames_batched(frames(&src), RENDER_BATCH, move |img| {
+                                Ascii::buffer(&ascii_options, img)
+                            })
+                        }
+                    },
+                    ascii_options.size,
+                    options,
+                    &player_options,
+                    signals,
+                    stdout,
+                )
             } else {
                 write_still(
-                    Ascii::img(&ascii_options, &src.img()).into_iter(),
-                    options.truecolor,
-                );
+                    &Ascii::buffer(ascii_options, &src.img()?),
+                    options,
+                    stylesheet,
+                    stdout,
+                )
             }
         }
         RendererOption::Braille(braille_options) => {
-            if src.has_frames() && animated {
+            if animated {
+                src.frames()?;
                 write_animated(
-                    Braille::animated(&braille_options, src.frames()),
-                    options.truecolor,
-                );
+                    |size| {
+                        let src = src.clone();
+                        let braille_options = BrailleOptions {
+                            size,
+                            ..*braille_options
+                        };
+                        move || {
+                            render_frames_batched(frames(&src), RENDER_BATCH, move |img| {
+                                Braille::buffer(&braille_options, img)
+                            })
+                        }
+                    },
+                    braille_options.size,
+                    options,
+                    &player_options,
+                    signals,
+                    stdout,
+                )
             } else {
                 write_still(
-                    Braille::img(&braille_options, &src.img()).into_iter(),
-                    options.truecolor,
-                );
+                    &Braille::buffer(braille_options, &src.img()?),
+                    options,
+                    stylesheet,
+                    stdout,
+                )
             }
         }
         RendererOption::Kitty(kitty_options) => {
-            if let Err(e) = Kitty::img(&kitty_options, &src.img(), &mut stdout) {
-                eprintln!("An error occurred printing image: {}", e)
-            }
+            let result = if animated {
+                Kitty::animated(
+                    kitty_options,
+                    src.frames()?.flatten(),
+                    player_options.loops,
+                    stdout,
+                )
+            } else {
+                Kitty::img(kitty_options, &src.img()?, stdout)
+            };
+            result.map_err(|e| format!("Unable to print image: {}", e))
         }
         RendererOption::Iterm(iterm_options) => {
-            let err = if src.has_path() {
-                Iterm::path(&iterm_options, src.path())
+            let result = if src.has_path() {
+                Iterm::path(iterm_options, src.path())
             } else {
-                Iterm::data(&iterm_options, &src.raw())
+                Iterm::data(iterm_options, src.raw())
             };
-            if let Err(e) = err {
-                eprintln!("An error occurred printing image: {}", e)
-            }
+            result.map_err(|e| format!("Unable to print image: {}", e))
         }
     }
 }
 
-/// Returns a Arc reference to a boolean value that is set to true when a "exit"
-/// signal is recieved (currently INT, QUIT, TERM, and WINCH).
-fn get_quit_hook() -> Arc<AtomicBool> {
-    let atomic = Arc::new(AtomicBool::new(false));
-    for signal in &[
-        signal_hook::consts::SIGINT,
-        signal_hook::consts::SIGQUIT,
-        signal_hook::consts::SIGTERM,
-        signal_hook::consts::SIGWINCH,
-    ] {
-        signal_hook::flag::register(*signal, Arc::clone(&atomic))
-            .expect("Unable to hook a termination signal");
-    }
-    atomic
+/// Decoded frames of an animation that was already checked to decode
+fn frames(src: &ImageSource) -> impl Iterator<Item = Frame> {
+    src.frames().into_iter().flatten().flatten()
 }
 
-fn write_still(iter: impl Iterator<Item = impl Iterator<Item = impl TermWriter>>, truecolor: bool) {
-    let stdout = std::io::stdout();
-    let mut stdout = stdout.lock();
-    for row in iter {
-        for block in row {
-            let _ = block.write(truecolor, &mut stdout);
+/// Flags set by signals while animations play
+struct Signals {
+    /// Set by any of the hooked signals, stops the player
+    interrupted: Arc<AtomicBool>,
+    /// Set by an "exit" signal (currently QUIT and TERM), INT only stops the current file
+    quit: Arc<AtomicBool>,
+    /// Set by WINCH when the terminal is resized
+    resized: Arc<AtomicBool>,
+}
+
+impl Signals {
+    /// Hook INT, the exit signals, and WINCH if `resize` is set
+    fn hook(resize: bool) -> Self {
+        let signals = Signals {
+            interrupted: Arc::new(AtomicBool::new(false)),
+            quit: Arc::new(AtomicBool::new(false)),
+            resized: Arc::new(AtomicBool::new(false)),
+        };
+        signal_hook::flag::register(
+            signal_hook::consts::SIGINT,
+            Arc::clone(&signals.interrupted),
+        )
+        .expect("Unable to hook the interrupt signal");
+        for signal in &[signal_hook::consts::SIGQUIT, signal_hook::consts::SIGTERM] {
+            signal_hook::flag::register(*signal, Arc::clone(&signals.quit))
+                .and_then(|_| {
+                    signal_hook::flag::register(*signal, Arc::clone(&signals.interrupted))
+                })
+                .expect("Unable to hook a termination signal");
+        }
+        if resize {
+            let signal = signal_hook::consts::SIGWINCH;
+            signal_hook::flag::register(signal, Arc::clone(&signals.resized))
+                .and_then(|_| signal_hook::flag::register(signal, Arc::clone(&signals.interrupted)))
+                .expect("Unable to hook the resize signal");
         }
-        let _ = queue!(stdout, crossterm::style::ResetColor);
-        let _ = writeln!(stdout);
+        signals
     }
-    let _ = stdout.flush();
 }
 
-fn write_animated(
-    frames: impl Iterator<
-        Item = (
-            Delay,
-            IntoChunks<impl Iterator<Item = impl TermWriter + Clone>>,
-        ),
-    >,
-    truecolor: bool,
-) {
-    let stdout = std::io::stdout();
-    let mut stdout = stdout.lock();
-    let iter = frames.collect::<Vec<_>>();
-    let iter = iter
-        .into_iter()
-        .map(|(delay, frame)| {
-            (
-                delay,
-                frame
-                    .into_iter()
-                    .map(|row| row.collect::<Vec<_>>())
-                    .collect::<Vec<_>>(),
-            )
-        })
-        .collect::<Vec<_>>();
-
-    let stopping = get_quit_hook();
-    let _ = queue!(
-        stdout,
-        terminal::Clear(terminal::ClearType::All),
-        cursor::Hide
-    );
-
-    // TODO: Hide cursor, save cursor
-    for (delay, frame) in iter.into_iter().cycle() {
-        if stopping.load(Ordering::Relaxed) {
-            break;
-        }
-        let _ = queue!(stdout, cursor::MoveTo(0, 0));
-        for row in frame.into_iter() {
-            for block in row {
-                let _ = block.write(truecolor, &mut stdout);
+fn write_still(
+    buffer: &CellBuffer<impl TermWriter + Clone>,
+    options: &Options,
+    stylesheet: &mut Option<Stylesheet>,
+    stdout: &mut impl Write,
+) -> Result<(), String> {
+    let result = match options.export.as_ref().map(|export| &export.format) {
+        Some(ExportFormat::Text) => export::write_text(buffer, stdout),
+        Some(ExportFormat::Html(html_options)) => {
+            if let Some(stylesheet) = stylesheet {
+                stylesheet.add(buffer);
             }
-            let _ = queue!(stdout, style::ResetColor);
-            let _ = writeln!(stdout);
+            Html::write_pre(html_options, buffer, stdout)
+        }
+        Some(ExportFormat::Svg(svg_options)) => Svg::write(svg_options, buffer, stdout),
+        Some(ExportFormat::Asciicast) => Asciicast::write(
+            &cast_options(options, Some(1)).expect("exporting an asciicast"),
+            iter::once((Delay::from_numer_denom_ms(0, 1), buffer.clone())),
+            stdout,
+        ),
+        Some(ExportFormat::Ansi) | None => write_buffer(buffer, options.truecolor, stdout),
+    };
+    result
+        .and_then(|_| stdout.flush())
+        .map_err(|e| format!("Unable to print image: {}", e))
+}
+
+/// Options for recording an asciicast, or `None` when not exporting one
+fn cast_options(options: &Options, loops: Option<u32>) -> Option<CastOptions> {
+    match options.export.as_ref()?.format {
+        ExportFormat::Asciicast => Some(CastOptions {
+            truecolor: options.truecolor,
+            speed: options.speed,
+            max_fps: options.fps,
+            // A player can loop the recording instead
+            loops: loops.unwrap_or(1),
+            title: options.paths.first().cloned(),
+        }),
+        _ => None,
+    }
+}
+
+/// Play an animation rendered by `frames` at `size`, rendering it again at the new size whenever
+/// the terminal is resized and carrying on from the same frame, or record it when exporting an
+/// asciicast
+fn write_animated<C, I, F>(
+    frames: impl Fn((u16, u16)) -> F,
+    size: (u16, u16),
+    options: &Options,
+    player_options: &PlayerOptions,
+    signals: &mut Option<Signals>,
+    stdout: &mut impl Write,
+) -> Result<(), String>
+where
+    C: TermWriter + Send + 'static,
+    I: Iterator<Item = (Delay, CellBuffer<C>)>,
+    F: Fn() -> I + Send + Sync + 'static,
+{
+    if let Some(cast_options) = cast_options(options, player_options.loops) {
+        return Asciicast::write(&cast_options, frames(size)(), stdout)
+            .and_then(|_| stdout.flush())
+            .map_err(|e| format!("Unable to record animation: {}", e));
+    }
+
+    let follow_terminal = options.follow_terminal;
+    let signals = signals.get_or_insert_with(|| Signals::hook(follow_terminal.is_some()));
+    let _guard = TerminalGuard::new(GuardOptions {
+        hide_cursor: true,
+        ..Default::default()
+    })
+    .map_err(|e| format!("Unable to prepare the terminal: {}", e))?;
+
+    let mut size = size;
+    let mut position = Position::default();
+    loop {
+        position = Player::play_from(
+            player_options,
+            position,
+            frames(size),
+            stdout,
+            &signals.interrupted,
+        )
+        .map_err(|e| format!("Unable to play animation: {}", e))?;
+        if signals.quit.load(Ordering::Relaxed) || !signals.resized.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        signals.interrupted.store(false, Ordering::Relaxed);
+        if let Some(new_size) = follow_terminal.and_then(args::fill_terminal) {
+            size = new_size;
         }
-        let _ = stdout.flush();
-        std::thread::sleep(Duration::from(delay));
     }
-    let _ = queue!(
-        stdout,
-        terminal::Clear(terminal::ClearType::All),
-        cursor::Show
-    );
 }
@@ -0,0 +1,340 @@
+//! Interactive full screen viewer with zoom and pan
+
+use crate::{
+    args::{Options, RendererOption},
+    img_src::ImageSource,
+};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    queue, style, terminal,
+};
+use image::DynamicImage;
+use std::{io::Write, path::Path};
+use term_image::{
+    ascii::{Ascii, AsciiOptions},
+    block::{Block, BlockOptions},
+    braille::{Braille, BrailleOptions},
+    kitty::Kitty,
+    raster, CellBuffer,
+};
+use term_image_crossterm::{
+    guard::{GuardOptions, TerminalGuard},
+    write_buffer_at, TermWriter,
+};
+
+/// How much each zoom step scales the image
+const ZOOM_STEP: f64 = 1.25;
+/// Fraction of the view moved by each pan step
+const PAN_STEP: f64 = 0.125;
+
+const HELP: &str = "hjkl/arrows pan  +/- zoom  f fit  n/p next/prev  q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Zoom {
+    /// Fit the whole image in the view
+    Fit,
+    /// Cell columns per image pixel, rows are twice as tall
+    ///
+    /// Shown as a percentage of [`Viewer::actual_size`]
+    Scale(f64),
+}
+
+struct Viewer<'a> {
+    options: &'a Options,
+    index: usize,
+    image: Result<DynamicImage, String>,
+    zoom: Zoom,
+    /// Top left corner of the view in image pixels
+    pan: (f64, f64),
+    failed: Vec<&'a String>,
+}
+
+/// Browse every file in the alternate screen until quit, returning the files that couldn't be shown
+pub fn run<'a>(options: &'a Options, out: &mut impl Write) -> Vec<&'a String> {
+    let mut viewer = Viewer::new(options);
+    let result = TerminalGuard::new(GuardOptions {
+        alternate_screen: true,
+        raw_mode: true,
+        hide_cursor: true,
+        restore_position: false,
+    })
+    .and_then(|_guard| {
+        let result = viewer.run(out);
+        if let RendererOption::Kitty(_) = options.renderer_options {
+            Kitty::clear(out)?;
+            out.flush()?;
+        }
+        result
+    });
+    if let Err(e) = result {
+        eprintln!("Error occurred in the viewer: {}", e);
+    }
+    viewer.failed
+}
+
+impl<'a> Viewer<'a> {
+    fn new(options: &'a Options) -> Self {
+        let mut viewer = Viewer {
+            options,
+            index: 0,
+            image: Err(String::new()),
+            zoom: Zoom::Fit,
+            pan: (0., 0.),
+            failed: Vec::new(),
+        };
+        viewer.load();
+        viewer
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> crossterm::Result<()> {
+        loop {
+            self.draw(out)?;
+            match event::read()? {
+                Event::Key(key) if !self.handle(key) => return Ok(()),
+                // The view size is read on every draw, so a resize only needs the pan clamped
+                Event::Resize(..) => self.clamp_pan(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Load the current file, showing the whole image
+    fn load(&mut self) {
+        let options = self.options;
+        let path = &options.paths[self.index];
+        // The visible part is cropped and encoded on every redraw, so grayscale and 16 bit images
+        // are converted once to the 8 bit RGBA kitty takes
+        self.image = ImageSource::new(path.clone())
+            .and_then(|src| src.img())
+            .map(|img| match img {
+                DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => img,
+                img => DynamicImage::ImageRgba8(img.to_rgba8()),
+            });
+        if self.image.is_err() && !self.failed.contains(&path) {
+            self.failed.push(path);
+        }
+        self.zoom = Zoom::Fit;
+        self.pan = (0., 0.);
+    }
+
+    /// Handle a key press, returning false to quit
+    fn handle(&mut self, key: KeyEvent) -> bool {
+        let count = self.options.paths.len();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Left | KeyCode::Char('h') => self.pan_by(-PAN_STEP, 0.),
+            KeyCode::Right | KeyCode::Char('l') => self.pan_by(PAN_STEP, 0.),
+            KeyCode::Up | KeyCode::Char('k') => self.pan_by(0., -PAN_STEP),
+            KeyCode::Down | KeyCode::Char('j') => self.pan_by(0., PAN_STEP),
+            KeyCode::Char('+') | KeyCode::Char('=') => self.zoom_by(ZOOM_STEP),
+            KeyCode::Char('-') | KeyCode::Char('_') => self.zoom_by(1. / ZOOM_STEP),
+            KeyCode::Char('f') => {
+                self.zoom = match self.zoom {
+                    Zoom::Fit => Zoom::Scale(self.actual_size()),
+                    Zoom::Scale(_) => Zoom::Fit,
+                };
+                self.pan = (0., 0.);
+            }
+            KeyCode::Char('n') | KeyCode::Char(' ') | KeyCode::PageDown => {
+                self.index = (self.index + 1) % count;
+                self.load();
+            }
+            KeyCode::Char('p') | KeyCode::Backspace | KeyCode::PageUp => {
+                self.index = (self.index + count - 1) % count;
+                self.load();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// The size of the view in cells, leaving the last row for the status bar
+    fn view_size() -> (u16, u16) {
+        let (columns, rows) = terminal::size().unwrap_or((80, 25));
+        (columns.max(1), rows.saturating_sub(1).max(1))
+    }
+
+    /// The scale that shows each image pixel as one pixel of a rendered cell, like the 4x8 pixels
+    /// of a block
+    ///
+    /// Kitty images are scaled by the terminal, so they are shown at one pixel per column.
+    fn actual_size(&self) -> f64 {
+        let cell_width = match &self.options.renderer_options {
+            RendererOption::Block(_) => raster::BLOCK_CELL_SIZE.0,
+            RendererOption::Braille(_) => raster::BRAILLE_CELL_SIZE.0,
+            RendererOption::Ascii(ascii_options) => ascii_options.cell_size().0,
+            RendererOption::Kitty(_) | RendererOption::Iterm(_) => 1,
+        };
+        1. / f64::from(cell_width)
+    }
+
+    /// Cell columns per image pixel
+    fn scale(&self) -> f64 {
+        match (self.zoom, &self.image) {
+            (Zoom::Scale(scale), _) => scale,
+            (Zoom::Fit, Ok(img)) => {
+                let (columns, rows) = Self::view_size();
+                (f64::from(columns) / f64::from(img.width()))
+                    .min(2. * f64::from(rows) / f64::from(img.height()))
+            }
+            (Zoom::Fit, Err(_)) => 1.,
+        }
+    }
+
+    /// The size of the view in image pixels
+    fn visible_size(&self) -> (f64, f64) {
+        let (columns, rows) = Self::view_size();
+        let scale = self.scale();
+        (f64::from(columns) / scale, 2. * f64::from(rows) / scale)
+    }
+
+    fn pan_by(&mut self, x: f64, y: f64) {
+        let (width, height) = self.visible_size();
+        self.pan.0 += x * width;
+        self.pan.1 += y * height;
+        self.clamp_pan();
+    }
+
+    /// Zoom by `factor`, keeping the center of the view in place
+    fn zoom_by(&mut self, factor: f64) {
+        let (width, height) = self.visible_size();
+        let center = (self.pan.0 + width / 2., self.pan.1 + height / 2.);
+        self.zoom = Zoom::Scale((self.scale() * factor).clamp(1. / 64., 64.));
+        let (width, height) = self.visible_size();
+        self.pan = (center.0 - width / 2., center.1 - height / 2.);
+        self.clamp_pan();
+    }
+
+    fn clamp_pan(&mut self) {
+        if let Ok(img) = &self.image {
+            let (width, height) = self.visible_size();
+            self.pan.0 = self.pan.0.min(f64::from(img.width()) - width).max(0.);
+            self.pan.1 = self.pan.1.min(f64::from(img.height()) - height).max(0.);
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write) -> crossterm::Result<()> {
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        if let Ok(img) = &self.image {
+            self.draw_image(img, out)?;
+        }
+        self.draw_status(out)?;
+        out.flush()
+    }
+
+    /// Render the visible part of the image, centered in the view
+    fn draw_image(&self, img: &DynamicImage, out: &mut impl Write) -> crossterm::Result<()> {
+        let (columns, rows) = Self::view_size();
+        let scale = self.scale();
+        let (width, height) = self.visible_size();
+
+        let x = (self.pan.0.round() as u32).min(img.width().saturating_sub(1));
+        let y = (self.pan.1.round() as u32).min(img.height().saturating_sub(1));
+        let width = (width.ceil() as u32).clamp(1, img.width() - x);
+        let height = (height.ceil() as u32).clamp(1, img.height() - y);
+        let visible = img.crop_imm(x, y, width, height);
+
+        let size = (
+            ((f64::from(width) * scale).round() as u16).clamp(1, columns),
+            ((f64::from(height) * scale / 2.).round() as u16).clamp(1, rows),
+        );
+        let truecolor = self.options.truecolor;
+        match &self.options.renderer_options {
+            RendererOption::Block(block_options) => {
+                let block_options = BlockOptions {
+                    size,
+                    ..*block_options
+                };
+                draw_centered(&Block::buffer(&block_options, &visible), truecolor, out)
+            }
+            RendererOption::Ascii(ascii_options) => {
+                let ascii_options = AsciiOptions {
+                    size,
+                    ..ascii_options.clone()
+                };
+                draw_centered(&Ascii::buffer(&ascii_options, &visible), truecolor, out)
+            }
+            RendererOption::Braille(braille_options) => {
+                let braille_options = BrailleOptions {
+                    size,
+                    ..*braille_options
+                };
+                draw_centered(&Braille::buffer(&braille_options, &visible), truecolor, out)
+            }
+            RendererOption::Kitty(_) => {
+                Kitty::clear(out)?;
+                queue!(
+                    out,
+                    cursor::MoveTo((columns - size.0) / 2, (rows - size.1) / 2)
+                )?;
+                Kitty::img_cells(&visible, (size.0.into(), size.1.into()), out)?;
+                Ok(())
+            }
+            RendererOption::Iterm(_) => unreachable!("the viewer uses blocks instead of iterm"),
+        }
+    }
+
+    /// Draw the file name, position, size, and zoom on the last row
+    fn draw_status(&self, out: &mut impl Write) -> crossterm::Result<()> {
+        let (columns, rows) = Self::view_size();
+        let path = &self.options.paths[self.index];
+        let name = Path::new(path)
+            .file_name()
+            .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned());
+        let details = match &self.image {
+            Ok(img) => format!(
+                "{}x{}  {}",
+                img.width(),
+                img.height(),
+                match self.zoom {
+                    Zoom::Fit => "fit".into(),
+                    Zoom::Scale(scale) => format!("{:.0}%", scale / self.actual_size() * 100.),
+                }
+            ),
+            Err(e) => e.clone(),
+        };
+        let status = format!(
+            " {} [{}/{}]  {}",
+            name,
+            self.index + 1,
+            self.options.paths.len(),
+            details
+        );
+
+        let columns = usize::from(columns);
+        let status_width = status.chars().count();
+        let line = if status_width + HELP.len() + 2 <= columns {
+            format!(
+                "{}{:>width$} ",
+                status,
+                HELP,
+                width = columns - status_width - 1
+            )
+        } else {
+            format!("{:width$}", status, width = columns)
+        };
+
+        queue!(
+            out,
+            cursor::MoveTo(0, rows),
+            style::SetAttribute(style::Attribute::Reverse),
+            style::Print(line.chars().take(columns).collect::<String>()),
+            style::SetAttribute(style::Attribute::Reset)
+        )
+    }
+}
+
+fn draw_centered(
+    buffer: &CellBuffer<impl TermWriter>,
+    truecolor: bool,
+    out: &mut impl Write,
+) -> crossterm::Result<()> {
+    let (columns, rows) = Viewer::view_size();
+    let position = (
+        columns.saturating_sub(buffer.width() as u16) / 2,
+        rows.saturating_sub(buffer.height() as u16) / 2,
+    );
+    write_buffer_at(buffer, position, truecolor, out)
+}
@@ -0,0 +1,160 @@
+//! Redraw files in place whenever they change
+
+use crate::{
+    args::{Options, RendererOption},
+    img_src::ImageSource,
+    show, Signals,
+};
+use crossterm::{cursor, queue, terminal};
+use std::{
+    fs,
+    io::Write,
+    sync::atomic::AtomicBool,
+    time::{Duration, Instant, SystemTime},
+};
+use term_image::kitty::Kitty;
+use term_image_crossterm::{
+    guard::{GuardOptions, TerminalGuard},
+    player::wait_until,
+};
+
+/// How often the files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a file has to stay the same before it is read, so partial writes are skipped
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// The modification time and length of a file, `None` if it can't be read (or is stdin)
+type Stamp = Option<(SystemTime, u64)>;
+
+struct WatchedFile<'a> {
+    path: &'a String,
+    /// The stamp of the last version that was read, `None` before the first read
+    stamp: Option<Stamp>,
+    /// The last version that decoded
+    last_good: Option<ImageSource>,
+    /// Why the last version read couldn't be shown
+    error: Option<String>,
+}
+
+/// Show every file, then redraw them whenever one changes until interrupted
+///
+/// Returns the files that were never shown.
+pub fn watch<'a>(options: &'a Options, stdout: &mut impl Write) -> Vec<&'a String> {
+    let signals = Signals::hook(false);
+    let mut files = options
+        .paths
+        .iter()
+        .map(|path| WatchedFile {
+            path,
+            stamp: None,
+            last_good: None,
+            error: None,
+        })
+        .collect::<Vec<_>>();
+
+    let result = TerminalGuard::new(GuardOptions {
+        hide_cursor: true,
+        ..Default::default()
+    })
+    .and_then(|_guard| loop {
+        let mut changed = false;
+        for file in &mut files {
+            changed |= file.update(&signals.interrupted);
+        }
+        if changed {
+            draw(options, &files, stdout)?;
+        }
+        // Any of the hooked signals stops watching, including INT
+        if !wait_until(Instant::now() + POLL_INTERVAL, &signals.interrupted) {
+            return Ok(());
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("Error occurred watching files: {}", e);
+    }
+
+    files
+        .iter()
+        .filter(|file| file.last_good.is_none())
+        .map(|file| file.path)
+        .collect()
+}
+
+impl WatchedFile<'_> {
+    /// Read the file again if it changed, returning whether it was read
+    fn update(&mut self, stop: &AtomicBool) -> bool {
+        let stamp = stamp(self.path);
+        if self.stamp == Some(stamp) {
+            return false;
+        }
+        self.stamp = Some(settle(self.path, stamp, stop));
+
+        match ImageSource::new(self.path.clone()).and_then(|src| src.img().map(|_| src)) {
+            Ok(src) => {
+                self.last_good = Some(src);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
+        true
+    }
+}
+
+/// Clear the screen and show the last good version of every file, with any errors below it
+fn draw(
+    options: &Options,
+    files: &[WatchedFile],
+    stdout: &mut impl Write,
+) -> crossterm::Result<()> {
+    if let RendererOption::Kitty(_) = options.renderer_options {
+        Kitty::clear(stdout)?;
+    }
+    queue!(
+        stdout,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, 0)
+    )?;
+    stdout.flush()?;
+
+    for file in files {
+        if options.header {
+            writeln!(stdout, "{}", file.path)?;
+            stdout.flush()?;
+        }
+        if let Some(src) = &file.last_good {
+            if let Err(e) = show(src, None, options, &mut None, &mut None, stdout) {
+                writeln!(stdout, "{}: {}", file.path, e)?;
+            }
+        }
+        match (&file.error, &file.last_good) {
+            (Some(e), Some(_)) => writeln!(
+                stdout,
+                "{}: {} (showing the last version that could be read)",
+                file.path, e
+            )?,
+            (Some(e), None) => writeln!(stdout, "{}: {}", file.path, e)?,
+            (None, _) => {}
+        }
+    }
+    stdout.flush()
+}
+
+fn stamp(path: &str) -> Stamp {
+    if path == "-" {
+        return None;
+    }
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Wait until the file stops changing, returning its final stamp
+fn settle(path: &str, mut stamp: Stamp, stop: &AtomicBool) -> Stamp {
+    while wait_until(Instant::now() + SETTLE_TIME, stop) {
+        let current = self::stamp(path);
+        if current == stamp {
+            break;
+        }
+        stamp = current;
+    }
+    stamp
+}
@@ -0,0 +1,219 @@
+//! Record animations as [asciicast v2](https://docs.asciinema.org/manual/asciicast/v2/) files
+//!
+//! Frames are timed from their delays instead of being recorded in real time, so playback is
+//! smooth and the same file is written every time.
+
+use crate::{write_buffer, TermWriter};
+use image::Delay;
+use std::io::Write;
+use term_image::{frame_duration, CellBuffer};
+
+/// Options for [`Asciicast`]
+pub struct CastOptions {
+    /// Use truecolor escapes
+    pub truecolor: bool,
+    /// Playback speed multiplier
+    pub speed: f64,
+    /// Frames per second to stay under, dropping frames like [`crate::player::Player`]
+    pub max_fps: Option<f64>,
+    /// Number of times the animation is recorded
+    pub loops: u32,
+    /// Title shown by players
+    pub title: Option<String>,
+}
+
+impl Default for CastOptions {
+    fn default() -> Self {
+        Self {
+            truecolor: false,
+            speed: 1.,
+            max_fps: None,
+            loops: 1,
+            title: None,
+        }
+    }
+}
+
+/// Writes rendered animations as asciicast recordings
+pub struct Asciicast;
+
+impl Asciicast {
+    /// Write `frames` to `out` as an asciicast, with a terminal just big enough for the frames
+    ///
+    /// Every frame is drawn from the top left corner, and the last frame stays on screen for its
+    /// whole delay.  With `max_fps`, a frame is left out when the next draw would come after it
+    /// ended, and is otherwise drawn late.
+    pub fn write<C: TermWriter>(
+        options: &CastOptions,
+        frames: impl IntoIterator<Item = (Delay, CellBuffer<C>)>,
+        out: &mut impl Write,
+    ) -> crossterm::Result<()> {
+        let mut size = (0, 0);
+        let mut events = Vec::new();
+        for (delay, frame) in frames {
+            size = (size.0.max(frame.width()), size.1.max(frame.height()));
+            let mut data = b"\x1b[H".to_vec();
+            write_buffer(&frame, options.truecolor, &mut data)?;
+            // Recordings hold what a terminal receives, where newlines have been turned into CRLF
+            let data = String::from_utf8_lossy(&data).replace('\n', "\r\n");
+            events.push((frame_duration(delay).as_secs_f64() / options.speed, data));
+        }
+
+        // The newline after the last row needs a row of its own so the frame doesn't scroll
+        write!(
+            out,
+            r#"{{"version": 2, "width": {}, "height": {}"#,
+            size.0,
+            size.1 + 1
+        )?;
+        if let Some(title) = &options.title {
+            write!(out, r#", "title": "{}""#, escape(title))?;
+        }
+        writeln!(out, "}}")?;
+
+        let interval = options.max_fps.map_or(0., |fps| 1. / fps);
+        let count = events.len() * options.loops.max(1) as usize;
+        let mut time = 0.;
+        let mut last_draw = None;
+        write_event(time, "\x1b[?25l\x1b[2J", out)?;
+        for (i, (duration, data)) in events.iter().cycle().take(count).enumerate() {
+            let end = time + duration;
+            let draw = last_draw.map_or(time, |last: f64| time.max(last + interval));
+            // The last frame is always drawn so the recording ends on it
+            if draw < end || i + 1 == count {
+                write_event(draw, data, out)?;
+                last_draw = Some(draw);
+            }
+            time = end;
+        }
+        write_event(
+            last_draw.map_or(time, |last| time.max(last)),
+            "\x1b[?25h",
+            out,
+        )?;
+        Ok(())
+    }
+}
+
+fn write_event(time: f64, data: &str, out: &mut impl Write) -> crossterm::Result<()> {
+    writeln!(out, r#"[{:.6}, "o", "{}"]"#, time, escape(data))?;
+    Ok(())
+}
+
+/// Escape `text` for a JSON string
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            ch if ch.is_control() => escaped.push_str(&format!("\\u{:04x}", u32::from(ch))),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use term_image::ascii::Cell;
+
+    fn frame(chars: &str, width: usize) -> (Delay, CellBuffer<Cell>) {
+        let cells = chars
+            .chars()
+            .map(|ch| Cell {
+                ch,
+                fg: None,
+                bg: None,
+            })
+            .collect();
+        (
+            Delay::from_numer_denom_ms(125, 1),
+            CellBuffer::new(width, cells),
+        )
+    }
+
+    /// The header line, and the time and escaped data of each event
+    fn record(options: &CastOptions) -> (String, Vec<(String, String)>) {
+        let frames = vec![frame("\"\\ab", 2), frame("x", 1), frame("y", 1)];
+        let mut out = Vec::new();
+        Asciicast::write(options, frames, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut lines = out.lines();
+        let header = lines.next().unwrap().to_string();
+        let events = lines
+            .map(|line| {
+                let line = line.strip_prefix('[').unwrap().strip_suffix("\"]").unwrap();
+                let (time, data) = line.split_once(", \"o\", \"").unwrap();
+                (time.to_string(), data.to_string())
+            })
+            .collect();
+        (header, events)
+    }
+
+    fn times(events: &[(String, String)]) -> Vec<&str> {
+        events.iter().map(|(time, _)| time.as_str()).collect()
+    }
+
+    #[test]
+    fn header_fits_the_largest_frame_and_escapes_the_title() {
+        let (header, _) = record(&CastOptions {
+            title: Some("a \"b\"".into()),
+            ..CastOptions::default()
+        });
+        assert_eq!(
+            header,
+            r#"{"version": 2, "width": 2, "height": 3, "title": "a \"b\""}"#
+        );
+    }
+
+    #[test]
+    fn frames_are_escaped_with_crlf_line_endings() {
+        let (_, events) = record(&CastOptions::default());
+        assert_eq!(events[0].1, r"\u001b[?25l\u001b[2J");
+        assert_eq!(events[1].1, r#"\u001b[H\"\\\u001b[0m\r\nab\u001b[0m\r\n"#);
+        assert_eq!(events[2].1, r"\u001b[Hx\u001b[0m\r\n");
+        assert_eq!(events[4].1, r"\u001b[?25h");
+    }
+
+    #[test]
+    fn events_are_timed_by_delay_and_speed() {
+        let (_, events) = record(&CastOptions {
+            speed: 0.5,
+            loops: 2,
+            ..CastOptions::default()
+        });
+        assert_eq!(
+            times(&events),
+            [
+                "0.000000", "0.000000", "0.250000", "0.500000", "0.750000", "1.000000", "1.250000",
+                "1.500000"
+            ]
+        );
+    }
+
+    #[test]
+    fn max_fps_drops_frames_but_keeps_the_last() {
+        let (_, events) = record(&CastOptions {
+            max_fps: Some(4.),
+            loops: 2,
+            ..CastOptions::default()
+        });
+        // Frames start every 0.125s and a draw can come every 0.25s, so every other frame is
+        // dropped and the last one is drawn late
+        assert_eq!(
+            times(&events),
+            ["0.000000", "0.000000", "0.250000", "0.500000", "0.750000", "0.750000"]
+        );
+        let frames = events[1..5]
+            .iter()
+            .map(|(_, data)| data.strip_prefix(r"\u001b[H").unwrap().chars().next());
+        // The first frame starts with an escaped quote
+        let frames = frames.collect::<Option<String>>();
+        assert_eq!(frames.as_deref(), Some("\\yxy"));
+    }
+}
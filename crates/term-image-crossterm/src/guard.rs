@@ -0,0 +1,178 @@
+//! Restore the terminal after drawing, even if the program panics
+
+use crossterm::{
+    cursor, execute,
+    style::{Attribute, ResetColor, SetAttribute},
+    terminal,
+};
+use std::{
+    io::{stdout, Write},
+    sync::{Mutex, Once},
+    thread::{self, ThreadId},
+};
+
+/// Every live guard and the thread that created it, restored by the panic hook from newest to oldest
+static ACTIVE: Mutex<Vec<(ThreadId, GuardOptions)>> = Mutex::new(Vec::new());
+static PANIC_HOOK: Once = Once::new();
+
+/// What a [`TerminalGuard`] changes, and so restores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GuardOptions {
+    /// Switch to the alternate screen
+    pub alternate_screen: bool,
+    /// Enable raw mode
+    pub raw_mode: bool,
+    /// Hide the cursor
+    pub hide_cursor: bool,
+    /// Move the cursor back to where it was when the guard was created
+    pub restore_position: bool,
+}
+
+/// Sets up the terminal on stdout, and restores the cursor, colors, and screen when dropped
+///
+/// A panic hook restores the terminal before the panic message is printed, so it stays readable.
+/// Only panics on the thread that created the guard restore it, other threads may still be drawing.
+pub struct TerminalGuard {
+    options: GuardOptions,
+}
+
+impl TerminalGuard {
+    pub fn new(options: GuardOptions) -> crossterm::Result<Self> {
+        PANIC_HOOK.call_once(install_panic_hook);
+
+        setup(&mut stdout(), &options)?;
+        if options.raw_mode {
+            terminal::enable_raw_mode()?;
+        }
+
+        if let Ok(mut active) = ACTIVE.lock() {
+            active.push((thread::current().id(), options));
+        }
+        Ok(Self { options })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        if let Ok(mut active) = ACTIVE.lock() {
+            let entry = (thread::current().id(), self.options);
+            if let Some(i) = active.iter().rposition(|active| *active == entry) {
+                active.remove(i);
+            }
+        }
+        let _ = restore(&mut stdout(), &self.options);
+    }
+}
+
+/// Restore the guards of a panicking thread, then run the hook that was set before
+fn install_panic_hook() {
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let current = thread::current().id();
+        if let Ok(mut active) = ACTIVE.lock() {
+            let (own, others) = active
+                .drain(..)
+                .partition::<Vec<_>, _>(|(thread, _)| *thread == current);
+            for (_, options) in own.iter().rev() {
+                let _ = restore(&mut stdout(), options);
+            }
+            *active = others;
+        }
+        previous_hook(info);
+    }));
+}
+
+/// Everything but raw mode, which isn't written to `out`
+fn setup(out: &mut impl Write, options: &GuardOptions) -> crossterm::Result<()> {
+    execute!(out, cursor::SavePosition)?;
+    if options.alternate_screen {
+        execute!(out, terminal::EnterAlternateScreen)?;
+    }
+    if options.hide_cursor {
+        execute!(out, cursor::Hide)?;
+    }
+    Ok(())
+}
+
+fn restore(out: &mut impl Write, options: &GuardOptions) -> crossterm::Result<()> {
+    execute!(
+        out,
+        ResetColor,
+        SetAttribute(Attribute::Reset),
+        cursor::Show
+    )?;
+    if options.alternate_screen {
+        execute!(out, terminal::LeaveAlternateScreen)?;
+    }
+    if options.restore_position {
+        execute!(out, cursor::RestorePosition)?;
+    }
+    if options.raw_mode {
+        terminal::disable_raw_mode()?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{atomic::AtomicBool, atomic::Ordering, Arc};
+
+    fn written(f: impl FnOnce(&mut Vec<u8>) -> crossterm::Result<()>) -> String {
+        let mut out = Vec::new();
+        f(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn defaults_restore_exactly_what_they_enable() {
+        // Only the position is saved, and restoring only resets the colors and cursor
+        let options = GuardOptions::default();
+        assert_eq!(written(|out| setup(out, &options)), "\x1b7");
+        assert_eq!(
+            written(|out| restore(out, &options)),
+            "\x1b[0m\x1b[0m\x1b[?25h"
+        );
+
+        let options = GuardOptions {
+            alternate_screen: true,
+            raw_mode: false,
+            hide_cursor: true,
+            restore_position: true,
+        };
+        assert_eq!(
+            written(|out| setup(out, &options)),
+            "\x1b7\x1b[?1049h\x1b[?25l"
+        );
+        assert_eq!(
+            written(|out| restore(out, &options)),
+            "\x1b[0m\x1b[0m\x1b[?25h\x1b[?1049l\x1b8"
+        );
+    }
+
+    #[test]
+    fn panic_hook_calls_the_previous_hook() {
+        let called = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&called);
+        std::panic::set_hook(Box::new(move |_| flag.store(true, Ordering::Relaxed)));
+        install_panic_hook();
+
+        // The panicking thread's guard is restored, guards of other threads are left alone
+        let entry = (thread::current().id(), GuardOptions::default());
+        ACTIVE.lock().unwrap().push(entry);
+        let panicking = thread::spawn(|| {
+            let entry = (thread::current().id(), GuardOptions::default());
+            ACTIVE.lock().unwrap().push(entry);
+            panic!("oops");
+        });
+        assert!(panicking.join().is_err());
+        // Back to the default hook
+        drop(std::panic::take_hook());
+
+        assert!(called.load(Ordering::Relaxed));
+        let mut active = ACTIVE.lock().unwrap();
+        assert_eq!(*active, [entry]);
+        active.clear();
+    }
+}
@@ -1,11 +1,19 @@
 use crossterm::{
+    cursor::MoveTo,
     queue,
-    style::{Color, SetBackgroundColor, SetForegroundColor},
+    style::{Color, ResetColor, SetBackgroundColor, SetForegroundColor},
 };
 use std::io::Write;
+use term_image::{CellBuffer, StyledCell};
+
+pub mod asciicast;
+pub mod guard;
+pub mod player;
 
 /// Print cells to the terminal with ansi 256-color escapes or truecolor (RGB) escapes
-pub trait TermWriter {
+///
+/// Cells only set the colors they have, see [`write_row`] for cells without colors.
+pub trait TermWriter: StyledCell {
     /// Write cell to `out`, optionally using truecolor
     fn write(&self, truecolor: bool, out: &mut impl Write) -> crossterm::Result<()> {
         if truecolor {
@@ -21,6 +29,57 @@ pub trait TermWriter {
     fn write_256(&self, out: &mut impl Write) -> crossterm::Result<()>;
 }
 
+/// Write every row of `buffer` to `out`, resetting the colors at the end of each row
+pub fn write_buffer(
+    buffer: &CellBuffer<impl TermWriter>,
+    truecolor: bool,
+    out: &mut impl Write,
+) -> crossterm::Result<()> {
+    for row in buffer.rows() {
+        write_row(row, truecolor, out)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Write `buffer` with its top left corner at `position` (column, row)
+///
+/// Rows are positioned with cursor movement instead of newlines, so this also works in raw mode.
+pub fn write_buffer_at(
+    buffer: &CellBuffer<impl TermWriter>,
+    position: (u16, u16),
+    truecolor: bool,
+    out: &mut impl Write,
+) -> crossterm::Result<()> {
+    for (y, row) in buffer.rows().enumerate() {
+        queue!(out, MoveTo(position.0, position.1 + y as u16))?;
+        write_row(row, truecolor, out)?;
+    }
+    Ok(())
+}
+
+/// Write a row of cells and reset the colors after it
+///
+/// Cells without a color are drawn in the terminal's default color, rather than whatever color
+/// the cell before them set.
+pub fn write_row(
+    row: &[impl TermWriter],
+    truecolor: bool,
+    out: &mut impl Write,
+) -> crossterm::Result<()> {
+    let mut colored = (false, false);
+    for cell in row {
+        let (fg, bg) = (cell.fg().is_some(), cell.bg().is_some());
+        if (colored.0 && !fg) || (colored.1 && !bg) {
+            queue!(out, ResetColor)?;
+            colored = (false, false);
+        }
+        cell.write(truecolor, out)?;
+        colored = (colored.0 || fg, colored.1 || bg);
+    }
+    queue!(out, ResetColor)
+}
+
 impl TermWriter for term_image::block::Cell {
     fn write_truecolor(&self, out: &mut impl Write) -> crossterm::Result<()> {
         queue!(
@@ -45,16 +104,23 @@ impl TermWriter for term_image::block::Cell {
 
 impl TermWriter for term_image::ascii::Cell {
     fn write_truecolor(&self, out: &mut impl Write) -> crossterm::Result<()> {
-        queue!(out, SetForegroundColor(Color::from(self.fg.0)))?;
+        if let Some(fg) = self.fg {
+            queue!(out, SetForegroundColor(Color::from(fg.0)))?;
+        }
+        if let Some(bg) = self.bg {
+            queue!(out, SetBackgroundColor(Color::from(bg.0)))?;
+        }
         write!(out, "{}", self.ch)?;
         Ok(())
     }
 
     fn write_256(&self, out: &mut impl Write) -> crossterm::Result<()> {
-        queue!(
-            out,
-            SetForegroundColor(Color::AnsiValue(self.fg.as_256().0))
-        )?;
+        if let Some(fg) = self.fg {
+            queue!(out, SetForegroundColor(Color::AnsiValue(fg.as_256().0)))?;
+        }
+        if let Some(bg) = self.bg {
+            queue!(out, SetBackgroundColor(Color::AnsiValue(bg.as_256().0)))?;
+        }
         write!(out, "{}", self.ch)?;
         Ok(())
     }
@@ -63,6 +129,9 @@ impl TermWriter for term_image::ascii::Cell {
 impl TermWriter for term_image::braille::Cell {
     fn write_truecolor(&self, out: &mut impl Write) -> crossterm::Result<()> {
         queue!(out, SetForegroundColor(Color::from(self.fg.0)))?;
+        if let Some(bg) = self.bg {
+            queue!(out, SetBackgroundColor(Color::from(bg.0)))?;
+        }
         write!(out, "{}", self.ch)?;
         Ok(())
     }
@@ -72,7 +141,85 @@ impl TermWriter for term_image::braille::Cell {
             out,
             SetForegroundColor(Color::AnsiValue(self.fg.as_256().0))
         )?;
+        if let Some(bg) = self.bg {
+            queue!(out, SetBackgroundColor(Color::AnsiValue(bg.as_256().0)))?;
+        }
         write!(out, "{}", self.ch)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use term_image::{ascii, block, Rgb};
+
+    const RED: Rgb = Rgb((255, 0, 0));
+    const BLUE: Rgb = Rgb((0, 0, 255));
+
+    fn written(write: impl FnOnce(&mut Vec<u8>) -> crossterm::Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn block_cells_set_both_colors() {
+        let cell = block::Cell {
+            ch: '▀',
+            fg: RED,
+            bg: BLUE,
+        };
+        let buffer = CellBuffer::new(1, vec![cell.clone(), cell]);
+        assert_eq!(
+            written(|out| write_buffer(&buffer, true, out)),
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\x1b[0m\n\
+             \x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\x1b[0m\n"
+        );
+        assert_eq!(
+            written(|out| write_buffer(&buffer, false, out)),
+            "\x1b[38;5;196m\x1b[48;5;21m▀\x1b[0m\n\x1b[38;5;196m\x1b[48;5;21m▀\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn cells_without_colors_reset_the_previous_colors() {
+        let row = [
+            ascii::Cell {
+                ch: 'a',
+                fg: Some(RED),
+                bg: Some(BLUE),
+            },
+            ascii::Cell {
+                ch: 'b',
+                fg: Some(BLUE),
+                bg: None,
+            },
+            ascii::Cell {
+                ch: 'c',
+                fg: None,
+                bg: None,
+            },
+        ];
+        assert_eq!(
+            written(|out| write_row(&row, true, out)),
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255ma\
+             \x1b[0m\x1b[38;2;0;0;255mb\
+             \x1b[0mc\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn rows_are_positioned_with_the_cursor() {
+        let cell = ascii::Cell {
+            ch: 'x',
+            fg: None,
+            bg: None,
+        };
+        let buffer = CellBuffer::new(1, vec![cell.clone(), cell]);
+        assert_eq!(
+            written(|out| write_buffer_at(&buffer, (2, 3), true, out)),
+            "\x1b[4;3Hx\x1b[0m\x1b[5;3Hx\x1b[0m"
+        );
+    }
+}
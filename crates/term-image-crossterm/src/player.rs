@@ -0,0 +1,588 @@
+//! Streaming animation playback
+//!
+//! Frames are rendered on a background thread while earlier frames are being shown, so playback
+//! starts as soon as the first frame is ready.  Rendered frames are kept for later loops as long
+//! as the whole animation fits in the cache budget, otherwise every loop is rendered again.
+//!
+//! Frames are scheduled against a monotonic clock, and frames are dropped when playback falls
+//! behind.
+
+use crate::{write_buffer, TermWriter};
+use crossterm::{cursor, queue, terminal};
+use image::Delay;
+use std::{
+    io::Write,
+    mem::{size_of, size_of_val},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{sync_channel, Receiver},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use term_image::{frame_duration, CellBuffer};
+
+/// Playback options for [`Player`]
+pub struct PlayerOptions {
+    /// Use truecolor escapes
+    pub truecolor: bool,
+    /// Number of frames that may be rendered ahead of the one being shown
+    pub render_ahead: usize,
+    /// Maximum size in bytes of the rendered frames kept for later loops
+    ///
+    /// This is an estimate: each frame counts its cells and its entry in the cache, but not any
+    /// spare capacity of its buffer or memory the cells point to.  Cells from the `term_image`
+    /// renderers don't point to any.
+    pub cache_budget: usize,
+    /// Playback speed multiplier
+    pub speed: f64,
+    /// Maximum number of frames drawn per second
+    pub max_fps: Option<f64>,
+    /// Number of times to play the animation, `None` to play until stopped
+    pub loops: Option<u32>,
+    /// Leave the last frame on screen after the final loop instead of clearing it
+    pub hold_last_frame: bool,
+    /// Play at the cursor using relative movement instead of clearing the screen, and leave the
+    /// last frame shown in the scrollback
+    pub inline: bool,
+    /// Line drawn above the animation, unless it plays inline
+    pub header: Option<String>,
+}
+
+impl Default for PlayerOptions {
+    fn default() -> Self {
+        Self {
+            truecolor: false,
+            render_ahead: 8,
+            cache_budget: 64 * 1024 * 1024,
+            speed: 1.,
+            max_fps: None,
+            loops: None,
+            hold_last_frame: false,
+            inline: false,
+            header: None,
+        }
+    }
+}
+
+/// How far playback got, to resume from after rendering the animation again
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Position {
+    /// Number of loops played in full
+    pub loops: u32,
+    /// Index of the frame being shown in the current loop
+    pub frame: usize,
+}
+
+/// Plays animations rendered to a [`CellBuffer`] per frame
+pub struct Player;
+
+impl Player {
+    /// Play an animation to `out` until every loop has played or `stop` is set
+    ///
+    /// `frames` is called once per loop (on a background thread) to produce the rendered frames,
+    /// and playback ends early if it produces none.
+    pub fn play<C, I, F>(
+        options: &PlayerOptions,
+        frames: F,
+        out: &mut impl Write,
+        stop: &AtomicBool,
+    ) -> crossterm::Result<()>
+    where
+        C: TermWriter + Send + 'static,
+        I: Iterator<Item = (Delay, CellBuffer<C>)>,
+        F: Fn() -> I + Send + Sync + 'static,
+    {
+        Self::play_from(options, Position::default(), frames, out, stop).map(|_| ())
+    }
+
+    /// Play an animation like [`Player::play`], starting at `start`, and return where playback
+    /// ended
+    ///
+    /// The frames before `start` are still rendered, but not shown.
+    pub fn play_from<C, I, F>(
+        options: &PlayerOptions,
+        start: Position,
+        frames: F,
+        out: &mut impl Write,
+        stop: &AtomicBool,
+    ) -> crossterm::Result<Position>
+    where
+        C: TermWriter + Send + 'static,
+        I: Iterator<Item = (Delay, CellBuffer<C>)>,
+        F: Fn() -> I + Send + Sync + 'static,
+    {
+        if !options.inline {
+            queue!(out, terminal::Clear(terminal::ClearType::All))?;
+            if let Some(header) = &options.header {
+                queue!(out, cursor::MoveTo(0, 0))?;
+                write!(out, "{}", header)?;
+            }
+        }
+        queue!(out, cursor::Hide)?;
+        let result = Self::run(options, start, Arc::new(frames), out, stop);
+        let keep_last_frame = options.hold_last_frame && matches!(result, Ok((true, _)));
+        if !(options.inline || keep_last_frame) {
+            queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        }
+        queue!(out, cursor::Show)?;
+        out.flush()?;
+        result.map(|(_, position)| position)
+    }
+
+    /// Returns whether every loop was played, and where playback ended
+    fn run<C, I, F>(
+        options: &PlayerOptions,
+        start: Position,
+        frames: Arc<F>,
+        out: &mut impl Write,
+        stop: &AtomicBool,
+    ) -> crossterm::Result<(bool, Position)>
+    where
+        C: TermWriter + Send + 'static,
+        I: Iterator<Item = (Delay, CellBuffer<C>)>,
+        F: Fn() -> I + Send + Sync + 'static,
+    {
+        let mut schedule = Schedule::new(options);
+        let mut cache = Vec::new();
+        let mut cache_size = 0;
+        let mut cache_complete = true;
+        let mut position = start;
+        // Frames of the current loop that were shown before playback started
+        let mut skip = start.frame;
+
+        loop {
+            let rx = Self::spawn(options, &frames);
+            let mut next = rx.recv().ok();
+            if next.is_none() {
+                return Ok((false, position));
+            }
+
+            let mut i = 0;
+            while let Some((delay, frame)) = next.take() {
+                next = rx.try_recv().ok();
+                let has_next = next.is_some();
+                if i >= skip {
+                    position.frame = i;
+                    if !schedule.show(&frame, delay, has_next, out, stop)? {
+                        return Ok((false, position));
+                    }
+                }
+                if !has_next {
+                    next = rx.recv().ok();
+                }
+                i += 1;
+
+                if cache_complete {
+                    cache_size += cached_size(&frame);
+                    if cache_size > options.cache_budget {
+                        cache_complete = false;
+                        cache = Vec::new();
+                    } else {
+                        cache.push((delay, frame));
+                    }
+                }
+            }
+
+            skip = 0;
+            position = Position {
+                loops: position.loops + 1,
+                frame: 0,
+            };
+            if options.loops.is_some_and(|loops| position.loops >= loops) {
+                return Ok((true, position));
+            }
+
+            if cache_complete {
+                loop {
+                    let last_loop = options
+                        .loops
+                        .is_some_and(|loops| position.loops + 1 >= loops);
+                    for (i, (delay, frame)) in cache.iter().enumerate() {
+                        let has_next = !last_loop || i + 1 < cache.len();
+                        position.frame = i;
+                        if !schedule.show(frame, *delay, has_next, out, stop)? {
+                            return Ok((false, position));
+                        }
+                    }
+                    position = Position {
+                        loops: position.loops + 1,
+                        frame: 0,
+                    };
+                    if last_loop {
+                        return Ok((true, position));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Render one loop of the animation on a background thread
+    ///
+    /// The thread exits once the receiver is dropped.
+    fn spawn<C, I, F>(options: &PlayerOptions, frames: &Arc<F>) -> Receiver<(Delay, CellBuffer<C>)>
+    where
+        C: Send + 'static,
+        I: Iterator<Item = (Delay, CellBuffer<C>)>,
+        F: Fn() -> I + Send + Sync + 'static,
+    {
+        let (tx, rx) = sync_channel(options.render_ahead);
+        let frames = Arc::clone(frames);
+        std::thread::spawn(move || {
+            for frame in frames() {
+                if tx.send(frame).is_err() {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+}
+
+/// Tracks when each frame should be shown
+struct Schedule<'a> {
+    options: &'a PlayerOptions,
+    /// When the current frame ends and the next one should be shown
+    deadline: Option<Instant>,
+    last_draw: Option<Instant>,
+    /// Rows of the last frame drawn inline, which the cursor is left below
+    inline_height: Option<usize>,
+}
+
+impl<'a> Schedule<'a> {
+    fn new(options: &'a PlayerOptions) -> Self {
+        Self {
+            options,
+            deadline: None,
+            last_draw: None,
+            inline_height: None,
+        }
+    }
+
+    /// How long a frame with `delay` is shown for at the playback speed
+    fn duration(&self, delay: Delay) -> Duration {
+        bounded_duration(frame_duration(delay).as_secs_f64() / self.options.speed)
+    }
+
+    /// Draw `frame` at its scheduled time and wait until it ends
+    ///
+    /// The frame is skipped if it would already be over, or replaced before the frame rate allows
+    /// another draw, unless there is no `next` frame ready to take its place.  Returns false when
+    /// playback was stopped.
+    fn show(
+        &mut self,
+        frame: &CellBuffer<impl TermWriter>,
+        delay: Delay,
+        has_next: bool,
+        out: &mut impl Write,
+        stop: &AtomicBool,
+    ) -> crossterm::Result<bool> {
+        if stop.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+
+        let (draw, end) = match self.next(Instant::now(), delay, has_next) {
+            Some(times) => times,
+            None => return Ok(true),
+        };
+        if !wait_until(draw, stop) {
+            return Ok(false);
+        }
+        if !self.options.inline {
+            let top = u16::from(self.options.header.is_some());
+            queue!(out, cursor::MoveTo(0, top))?;
+        } else if let Some(height) = self.inline_height.filter(|&height| height > 0) {
+            queue!(out, cursor::MoveToPreviousLine(height as u16))?;
+        }
+        write_buffer(frame, self.options.truecolor, out)?;
+        if self.options.inline {
+            self.inline_height = Some(frame.height());
+        }
+        out.flush()?;
+        self.drawn(Instant::now(), end);
+
+        Ok(wait_until(end, stop))
+    }
+
+    /// When to draw a frame with `delay` that comes up at `now`, and when it ends
+    ///
+    /// Returns `None` when the frame is skipped.
+    fn next(&mut self, now: Instant, delay: Delay, has_next: bool) -> Option<(Instant, Instant)> {
+        let start = self.deadline.unwrap_or(now);
+        let end = start + self.duration(delay);
+        let earliest_draw = match (self.options.max_fps, self.last_draw) {
+            (Some(fps), Some(last_draw)) => last_draw + bounded_duration(1. / fps),
+            _ => start,
+        };
+        self.deadline = Some(end);
+
+        if has_next && (now >= end || earliest_draw >= end) {
+            None
+        } else {
+            Some((start.max(earliest_draw), end))
+        }
+    }
+
+    /// Record that the frame ending at `end` was drawn at `now`
+    fn drawn(&mut self, now: Instant, end: Instant) {
+        self.last_draw = Some(now);
+        if now >= end {
+            // Nothing was ready to replace this frame, so start the next one from here
+            self.deadline = Some(now);
+        }
+    }
+}
+
+/// The estimated size of a frame in the cache, see [`PlayerOptions::cache_budget`]
+fn cached_size<C>(frame: &CellBuffer<C>) -> usize {
+    size_of::<(Delay, CellBuffer<C>)>() + size_of_val(frame.cells())
+}
+
+/// A duration of `secs` seconds, capped so extreme speeds and frame rates can't overflow the clock
+fn bounded_duration(secs: f64) -> Duration {
+    const MAX: Duration = Duration::from_secs(24 * 60 * 60);
+    Duration::try_from_secs_f64(secs).map_or(MAX, |duration| duration.min(MAX))
+}
+
+/// Sleep until `deadline`, returning false early if `stop` is set
+///
+/// `stop` is checked every 50ms, so a signal handler can set it.
+pub fn wait_until(deadline: Instant, stop: &AtomicBool) -> bool {
+    const STEP: Duration = Duration::from_millis(50);
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        std::thread::sleep((deadline - now).min(STEP));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use term_image::ascii::Cell;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn delay(ms: u32) -> Delay {
+        Delay::from_numer_denom_ms(ms, 1)
+    }
+
+    fn frame(ch: char) -> (Delay, CellBuffer<Cell>) {
+        let cell = Cell {
+            ch,
+            fg: None,
+            bg: None,
+        };
+        (delay(50), CellBuffer::new(1, vec![cell]))
+    }
+
+    /// Sets `stop` once `stop_after` frames were drawn, each draw ends with a flush
+    struct Screen<'a> {
+        out: Vec<u8>,
+        draws: usize,
+        stop_after: usize,
+        stop: &'a AtomicBool,
+    }
+
+    impl Write for Screen<'_> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.out.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.draws += 1;
+            if self.draws == self.stop_after {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+            Ok(())
+        }
+    }
+
+    /// Play the frames `x`, `y` and `z`, and return the output, where playback ended and how many
+    /// times the animation was rendered
+    fn play(
+        options: &PlayerOptions,
+        start: Position,
+        stop_after: usize,
+    ) -> (String, Position, usize) {
+        let renders = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&renders);
+        let frames = move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            "xyz".chars().map(frame)
+        };
+        let stop = AtomicBool::new(false);
+        let mut screen = Screen {
+            out: Vec::new(),
+            draws: 0,
+            stop_after,
+            stop: &stop,
+        };
+        let position = Player::play_from(options, start, frames, &mut screen, &stop).unwrap();
+        let out = String::from_utf8(screen.out).unwrap();
+        (out, position, renders.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn frames_follow_each_other_at_the_playback_speed() {
+        let t0 = Instant::now();
+        let options = PlayerOptions::default();
+        let mut schedule = Schedule::new(&options);
+        assert_eq!(
+            schedule.next(t0, delay(100), true),
+            Some((t0, t0 + ms(100)))
+        );
+        schedule.drawn(t0 + ms(5), t0 + ms(100));
+        // The next frame starts when the last one ends, not when it was drawn
+        assert_eq!(
+            schedule.next(t0 + ms(100), delay(100), true),
+            Some((t0 + ms(100), t0 + ms(200)))
+        );
+
+        let options = PlayerOptions {
+            speed: 2.,
+            ..PlayerOptions::default()
+        };
+        let mut schedule = Schedule::new(&options);
+        assert_eq!(schedule.next(t0, delay(100), true), Some((t0, t0 + ms(50))));
+        // Short delays are shown like browsers do
+        assert_eq!(schedule.duration(delay(0)), ms(50));
+
+        let options = PlayerOptions {
+            speed: 0.,
+            ..PlayerOptions::default()
+        };
+        assert_eq!(
+            Schedule::new(&options).duration(delay(100)),
+            Duration::from_secs(24 * 60 * 60)
+        );
+    }
+
+    #[test]
+    fn late_frames_are_skipped_unless_nothing_replaces_them() {
+        let t0 = Instant::now();
+        let options = PlayerOptions::default();
+        let mut schedule = Schedule::new(&options);
+        schedule.next(t0, delay(100), true);
+        schedule.drawn(t0, t0 + ms(100));
+        assert_eq!(schedule.next(t0 + ms(250), delay(100), true), None);
+        // Skipping keeps the following frames on time
+        assert_eq!(
+            schedule.next(t0 + ms(250), delay(100), true),
+            Some((t0 + ms(200), t0 + ms(300)))
+        );
+
+        let mut schedule = Schedule::new(&options);
+        schedule.next(t0, delay(100), true);
+        schedule.drawn(t0, t0 + ms(100));
+        assert_eq!(
+            schedule.next(t0 + ms(250), delay(100), false),
+            Some((t0 + ms(100), t0 + ms(200)))
+        );
+        // The frame was drawn late, so the next one is shown for its full delay
+        schedule.drawn(t0 + ms(250), t0 + ms(200));
+        assert_eq!(
+            schedule.next(t0 + ms(250), delay(100), true),
+            Some((t0 + ms(250), t0 + ms(350)))
+        );
+    }
+
+    #[test]
+    fn max_fps_drops_frames_between_draws() {
+        let t0 = Instant::now();
+        let options = PlayerOptions {
+            max_fps: Some(4.),
+            ..PlayerOptions::default()
+        };
+        let mut schedule = Schedule::new(&options);
+        assert_eq!(
+            schedule.next(t0, delay(100), true),
+            Some((t0, t0 + ms(100)))
+        );
+        schedule.drawn(t0, t0 + ms(100));
+        assert_eq!(schedule.next(t0 + ms(100), delay(100), true), None);
+        // Drawn late in its slot, once the frame rate allows it
+        assert_eq!(
+            schedule.next(t0 + ms(200), delay(100), true),
+            Some((t0 + ms(250), t0 + ms(300)))
+        );
+    }
+
+    #[test]
+    fn frames_are_cached_while_they_fit_the_budget() {
+        let size = "xyz"
+            .chars()
+            .map(|ch| cached_size(&frame(ch).1))
+            .sum::<usize>();
+        for &(cache_budget, renders) in &[(size, 1), (size - 1, 3), (0, 3)] {
+            let options = PlayerOptions {
+                cache_budget,
+                speed: 1000.,
+                loops: Some(3),
+                ..PlayerOptions::default()
+            };
+            let (_, position, count) = play(&options, Position::default(), 0);
+            assert_eq!(count, renders, "budget {}", cache_budget);
+            assert_eq!(position, Position { loops: 3, frame: 0 });
+        }
+    }
+    /// The frames drawn in `out`
+    fn drawn(out: &str) -> String {
+        out.chars().filter(|ch| "xyz".contains(*ch)).collect()
+    }
+
+    #[test]
+    fn position_moves_to_the_next_loop() {
+        for &cache_budget in &[usize::MAX, 0] {
+            let options = PlayerOptions {
+                cache_budget,
+                ..PlayerOptions::default()
+            };
+            let (out, position, _) = play(&options, Position::default(), 4);
+            assert_eq!(drawn(&out), "xyzx");
+            assert_eq!(position, Position { loops: 1, frame: 0 });
+
+            // Resuming skips the frames already shown in that loop
+            let options = PlayerOptions {
+                loops: Some(2),
+                ..options
+            };
+            let (out, position, _) = play(&options, Position { loops: 1, frame: 2 }, 0);
+            assert_eq!(drawn(&out), "z");
+            assert_eq!(position, Position { loops: 2, frame: 0 });
+        }
+    }
+
+    #[test]
+    fn last_frame_is_held_after_the_final_loop() {
+        let clear = "\u{1b}[2J";
+        for &hold_last_frame in &[true, false] {
+            let options = PlayerOptions {
+                loops: Some(2),
+                hold_last_frame,
+                ..PlayerOptions::default()
+            };
+            let (out, _, _) = play(&options, Position::default(), 0);
+            assert_eq!(drawn(&out), "xyzxyz");
+            let last_clear = out.rfind(clear).unwrap();
+            assert_eq!(last_clear > out.rfind('z').unwrap(), !hold_last_frame);
+        }
+
+        // Stopping early clears the frame anyway
+        let options = PlayerOptions {
+            loops: Some(2),
+            hold_last_frame: true,
+            ..PlayerOptions::default()
+        };
+        let (out, _, _) = play(&options, Position::default(), 2);
+        assert!(out.rfind(clear).unwrap() > out.rfind('y').unwrap());
+    }
+}
@@ -0,0 +1,305 @@
+//! Write rendered cells as HTML
+//!
+//! Each row becomes a line of a `<pre>`, and runs of cells with the same colors share a `<span>`.
+
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    io::{self, Write},
+};
+use term_image::{CellBuffer, Rgb, StyledCell};
+
+/// Class of every `<pre>` written
+pub const PRE_CLASS: &str = "term-image";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Styling {
+    /// Colors are set with `style` attributes
+    Inline,
+    /// Colors are set with classes like `fg-ff0000` and `bg-000000`, see [`Stylesheet`]
+    Classes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HtmlOptions {
+    pub styling: Styling,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        Self {
+            styling: Styling::Inline,
+        }
+    }
+}
+
+pub struct Html;
+
+impl Html {
+    /// Write `buffer` as a `<pre>` of colored `<span>`s
+    pub fn write_pre(
+        options: &HtmlOptions,
+        buffer: &CellBuffer<impl StyledCell>,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        match options.styling {
+            Styling::Inline => write!(out, r#"<pre class="{}" style="line-height:1">"#, PRE_CLASS)?,
+            Styling::Classes => write!(out, r#"<pre class="{}">"#, PRE_CLASS)?,
+        }
+
+        for (y, row) in buffer.rows().enumerate() {
+            if y > 0 {
+                writeln!(out)?;
+            }
+            let mut start = 0;
+            while start < row.len() {
+                let colors = (row[start].fg(), row[start].bg());
+                let len = row[start..]
+                    .iter()
+                    .take_while(|cell| (cell.fg(), cell.bg()) == colors)
+                    .count();
+                let text = row[start..start + len]
+                    .iter()
+                    .map(StyledCell::ch)
+                    .collect::<String>();
+                write_span(options, colors, &text, out)?;
+                start += len;
+            }
+        }
+
+        writeln!(out, "</pre>")
+    }
+
+    /// Write the CSS rules for the classes used by `buffer` with [`Styling::Classes`]
+    pub fn write_stylesheet(
+        buffer: &CellBuffer<impl StyledCell>,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        let mut stylesheet = Stylesheet::default();
+        stylesheet.add(buffer);
+        stylesheet.write(out)
+    }
+
+    /// Write a complete page showing `buffer`
+    pub fn write_document(
+        options: &HtmlOptions,
+        title: &str,
+        buffer: &CellBuffer<impl StyledCell>,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        let stylesheet = match options.styling {
+            Styling::Inline => None,
+            Styling::Classes => {
+                let mut stylesheet = Stylesheet::default();
+                stylesheet.add(buffer);
+                Some(stylesheet)
+            }
+        };
+        Self::write_document_start(title, stylesheet.as_ref(), out)?;
+        Self::write_pre(options, buffer, out)?;
+        Self::write_document_end(out)
+    }
+
+    /// Write everything in a page before its content, for pages showing several buffers
+    ///
+    /// `stylesheet` is written in the `<head>`, it should have every buffer on the page added.
+    pub fn write_document_start(
+        title: &str,
+        stylesheet: Option<&Stylesheet>,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html>")?;
+        writeln!(out, "<head>")?;
+        writeln!(out, r#"<meta charset="utf-8">"#)?;
+        writeln!(out, "<title>{}</title>", escape(title))?;
+        if let Some(stylesheet) = stylesheet {
+            writeln!(out, "<style>")?;
+            stylesheet.write(out)?;
+            writeln!(out, "</style>")?;
+        }
+        writeln!(out, "</head>")?;
+        writeln!(out, "<body>")
+    }
+
+    /// Write everything in a page after its content
+    pub fn write_document_end(out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "</body>")?;
+        writeln!(out, "</html>")
+    }
+}
+
+/// The classes used by one or more buffers with [`Styling::Classes`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stylesheet {
+    fg: BTreeSet<String>,
+    bg: BTreeSet<String>,
+}
+
+impl Stylesheet {
+    /// Add the colors of every cell in `buffer`
+    pub fn add(&mut self, buffer: &CellBuffer<impl StyledCell>) {
+        for cell in buffer.cells() {
+            self.fg.extend(cell.fg().map(hex));
+            self.bg.extend(cell.bg().map(hex));
+        }
+    }
+
+    /// Write the CSS rules for the classes, without a `<style>` element
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "pre.{} {{ line-height: 1; }}", PRE_CLASS)?;
+        for color in &self.fg {
+            writeln!(out, ".fg-{0} {{ color: #{0}; }}", color)?;
+        }
+        for color in &self.bg {
+            writeln!(out, ".bg-{0} {{ background-color: #{0}; }}", color)?;
+        }
+        Ok(())
+    }
+}
+
+/// Escape the characters that are special in HTML text and attributes
+pub fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(&['&', '<', '>', '"'][..]) {
+        return text.into();
+    }
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped.into()
+}
+
+fn write_span(
+    options: &HtmlOptions,
+    (fg, bg): (Option<Rgb>, Option<Rgb>),
+    text: &str,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    if fg.is_none() && bg.is_none() {
+        return write!(out, "{}", escape(text));
+    }
+
+    match options.styling {
+        Styling::Inline => {
+            let fg = fg.map(|fg| format!("color:#{};", hex(fg)));
+            let bg = bg.map(|bg| format!("background-color:#{};", hex(bg)));
+            write!(
+                out,
+                r#"<span style="{}{}">"#,
+                fg.unwrap_or_default(),
+                bg.unwrap_or_default()
+            )?;
+        }
+        Styling::Classes => {
+            let fg = fg.map(|fg| format!("fg-{}", hex(fg)));
+            let bg = bg.map(|bg| format!("bg-{}", hex(bg)));
+            let classes = fg.into_iter().chain(bg).collect::<Vec<_>>();
+            write!(out, r#"<span class="{}">"#, classes.join(" "))?;
+        }
+    }
+    write!(out, "{}</span>", escape(text))
+}
+
+fn hex(Rgb((r, g, b)): Rgb) -> String {
+    format!("{:02x}{:02x}{:02x}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use term_image::ascii::Cell;
+
+    const RED: Rgb = Rgb((255, 0, 0));
+    const BLUE: Rgb = Rgb((0, 0, 255));
+
+    fn buffer(width: usize, cells: &[(char, Option<Rgb>, Option<Rgb>)]) -> CellBuffer<Cell> {
+        let cells = cells
+            .iter()
+            .map(|&(ch, fg, bg)| Cell { ch, fg, bg })
+            .collect();
+        CellBuffer::new(width, cells)
+    }
+
+    fn written(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn runs_with_the_same_colors_share_a_span() {
+        let buffer = buffer(
+            3,
+            &[
+                ('a', Some(RED), None),
+                ('b', Some(RED), None),
+                ('c', Some(RED), Some(BLUE)),
+                ('d', None, None),
+                ('e', None, None),
+                ('f', Some(BLUE), None),
+            ],
+        );
+        let options = HtmlOptions::default();
+        assert_eq!(
+            written(|out| Html::write_pre(&options, &buffer, out)),
+            "<pre class=\"term-image\" style=\"line-height:1\">\
+             <span style=\"color:#ff0000;\">ab</span>\
+             <span style=\"color:#ff0000;background-color:#0000ff;\">c</span>\n\
+             de<span style=\"color:#0000ff;\">f</span></pre>\n"
+        );
+    }
+
+    #[test]
+    fn special_characters_are_escaped() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape(r#"<a & "b">"#), "&lt;a &amp; &quot;b&quot;&gt;");
+
+        let buffer = buffer(2, &[('<', Some(RED), None), ('&', None, None)]);
+        assert_eq!(
+            written(|out| Html::write_pre(&HtmlOptions::default(), &buffer, out)),
+            "<pre class=\"term-image\" style=\"line-height:1\">\
+             <span style=\"color:#ff0000;\">&lt;</span>&amp;</pre>\n"
+        );
+        let page = written(|out| Html::write_document_start("<title>", None, out));
+        assert!(page.contains("<title>&lt;title&gt;</title>"));
+    }
+
+    #[test]
+    fn classes_are_defined_once_in_the_head() {
+        let options = HtmlOptions {
+            styling: Styling::Classes,
+        };
+        let first = buffer(2, &[('a', Some(RED), Some(BLUE)), ('b', Some(BLUE), None)]);
+        let second = buffer(1, &[('c', Some(RED), None)]);
+        assert_eq!(
+            written(|out| Html::write_pre(&options, &first, out)),
+            "<pre class=\"term-image\">\
+             <span class=\"fg-ff0000 bg-0000ff\">a</span>\
+             <span class=\"fg-0000ff\">b</span></pre>\n"
+        );
+
+        let mut stylesheet = Stylesheet::default();
+        stylesheet.add(&first);
+        stylesheet.add(&second);
+        assert_eq!(
+            written(|out| stylesheet.write(out)),
+            "pre.term-image { line-height: 1; }\n\
+             .fg-0000ff { color: #0000ff; }\n\
+             .fg-ff0000 { color: #ff0000; }\n\
+             .bg-0000ff { background-color: #0000ff; }\n"
+        );
+
+        let page = written(|out| Html::write_document(&options, "page", &first, out));
+        let head = &page[..page.find("</head>").unwrap()];
+        assert_eq!(head.matches("<style>").count(), 1);
+        assert!(head.contains(".fg-ff0000 { color: #ff0000; }"));
+        assert!(page[head.len()..].contains("<span class=\"fg-ff0000 bg-0000ff\">a</span>"));
+    }
+}
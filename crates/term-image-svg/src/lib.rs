@@ -0,0 +1,202 @@
+//! Write rendered cells as SVG
+//!
+//! Each cell is drawn as a background `<rect>` with its glyph as `<text>` on top.  Runs of cells
+//! with the same background share a `<rect>`, and runs with the same foreground share a `<text>`.
+
+use std::io::{self, Write};
+use term_image::{CellBuffer, Rgb, StyledCell};
+use term_image_html::escape;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// CSS font family of the glyphs
+    pub font_family: String,
+    /// Size of each cell as (width, height) in SVG units, the font size is the cell height
+    pub cell_size: (f64, f64),
+    /// Color of glyphs without their own color
+    pub foreground: Rgb,
+    /// Color behind the whole image, `None` to leave it transparent
+    pub background: Option<Rgb>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            font_family: "monospace".into(),
+            cell_size: (8., 16.),
+            foreground: Rgb((255, 255, 255)),
+            background: Some(Rgb((0, 0, 0))),
+        }
+    }
+}
+
+pub struct Svg;
+
+impl Svg {
+    /// Write `buffer` as a standalone SVG image
+    pub fn write<C: StyledCell>(
+        options: &SvgOptions,
+        buffer: &CellBuffer<C>,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        let (cell_width, cell_height) = options.cell_size;
+        let width = buffer.width() as f64 * cell_width;
+        let height = buffer.height() as f64 * cell_height;
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+            width, height
+        )?;
+        if let Some(background) = options.background {
+            writeln!(
+                out,
+                r#"<rect width="{}" height="{}" fill="{}"/>"#,
+                width,
+                height,
+                hex(background)
+            )?;
+        }
+
+        writeln!(out, r#"<g shape-rendering="crispEdges">"#)?;
+        for (y, row) in buffer.rows().enumerate() {
+            for (start, len, bg) in runs(row, StyledCell::bg) {
+                if let Some(bg) = bg {
+                    writeln!(
+                        out,
+                        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                        start as f64 * cell_width,
+                        y as f64 * cell_height,
+                        len as f64 * cell_width,
+                        cell_height,
+                        hex(bg)
+                    )?;
+                }
+            }
+        }
+        writeln!(out, "</g>")?;
+
+        writeln!(
+            out,
+            r#"<g font-family="{}" font-size="{}" fill="{}">"#,
+            escape(&options.font_family),
+            cell_height,
+            hex(options.foreground)
+        )?;
+        // The baseline sits above the bottom of the cell, leaving room for descenders
+        let baseline = cell_height * 0.8;
+        for (y, row) in buffer.rows().enumerate() {
+            // Spaces split runs, since whitespace in <text> would shift the following glyphs
+            let glyph = |cell: &C| Some(cell.fg()).filter(|_| cell.ch() != ' ');
+            for (start, len, fg) in runs(row, glyph) {
+                let fg = match fg {
+                    Some(fg) => fg,
+                    None => continue,
+                };
+                let cells = &row[start..start + len];
+                let xs = (start..start + len)
+                    .map(|x| (x as f64 * cell_width).to_string())
+                    .collect::<Vec<_>>();
+                let text = cells.iter().map(StyledCell::ch).collect::<String>();
+                write!(
+                    out,
+                    r#"<text x="{}" y="{}""#,
+                    xs.join(" "),
+                    y as f64 * cell_height + baseline
+                )?;
+                if let Some(fg) = fg {
+                    write!(out, r#" fill="{}""#, hex(fg))?;
+                }
+                writeln!(out, ">{}</text>", escape(&text))?;
+            }
+        }
+        writeln!(out, "</g>")?;
+        writeln!(out, "</svg>")
+    }
+}
+
+/// Split `row` into runs of cells with the same `key`, as (start, length, key)
+fn runs<C, K: PartialEq>(row: &[C], key: impl Fn(&C) -> K) -> Vec<(usize, usize, K)> {
+    let mut runs: Vec<(usize, usize, K)> = Vec::new();
+    for (x, cell) in row.iter().enumerate() {
+        let cell_key = key(cell);
+        match runs.last_mut() {
+            Some((_, len, run_key)) if *run_key == cell_key => *len += 1,
+            _ => runs.push((x, 1, cell_key)),
+        }
+    }
+    runs
+}
+
+fn hex(Rgb((r, g, b)): Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use term_image::ascii::Cell;
+
+    const RED: Rgb = Rgb((255, 0, 0));
+    const BLUE: Rgb = Rgb((0, 0, 255));
+
+    fn written(options: &SvgOptions, buffer: &CellBuffer<Cell>) -> String {
+        let mut out = Vec::new();
+        Svg::write(options, buffer, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn cell(ch: char, fg: Option<Rgb>, bg: Option<Rgb>) -> Cell {
+        Cell { ch, fg, bg }
+    }
+
+    #[test]
+    fn backgrounds_are_merged_and_text_is_split_on_spaces() {
+        let buffer = CellBuffer::new(
+            5,
+            vec![
+                cell('a', Some(RED), Some(BLUE)),
+                cell('b', Some(RED), Some(BLUE)),
+                cell(' ', Some(RED), Some(BLUE)),
+                cell('c', Some(RED), None),
+                cell('<', None, None),
+                cell(' ', None, Some(RED)),
+                cell('d', None, Some(RED)),
+                cell('e', None, None),
+                cell(' ', None, None),
+                cell('f', Some(BLUE), None),
+            ],
+        );
+        let options = SvgOptions {
+            font_family: "Fira \"Code\"".into(),
+            cell_size: (6., 10.),
+            foreground: Rgb((1, 2, 3)),
+            background: None,
+        };
+        assert_eq!(
+            written(&options, &buffer),
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="30" height="20" viewBox="0 0 30 20">
+<g shape-rendering="crispEdges">
+<rect x="0" y="0" width="18" height="10" fill="#0000ff"/>
+<rect x="0" y="10" width="12" height="10" fill="#ff0000"/>
+</g>
+<g font-family="Fira &quot;Code&quot;" font-size="10" fill="#010203">
+<text x="0 6" y="8" fill="#ff0000">ab</text>
+<text x="18" y="8" fill="#ff0000">c</text>
+<text x="24" y="8">&lt;</text>
+<text x="6 12" y="18">de</text>
+<text x="24" y="18" fill="#0000ff">f</text>
+</g>
+</svg>
+"##
+        );
+    }
+
+    #[test]
+    fn default_options_use_a_black_background() {
+        let buffer = CellBuffer::new(1, vec![cell('a', None, None)]);
+        let svg = written(&SvgOptions::default(), &buffer);
+        assert!(svg.contains(r##"<rect width="8" height="16" fill="#000000"/>"##));
+        assert!(svg.contains(r##"<g font-family="monospace" font-size="16" fill="#ffffff">"##));
+        assert!(svg.contains(r#"<text x="0" y="12.8">a</text>"#));
+    }
+}
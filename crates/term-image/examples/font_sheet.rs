@@ -0,0 +1,74 @@
+//! Draw a sheet of glyphs from a TrueType or OpenType font, for `examples/glyph_masks.rs`
+//!
+//! ```text
+//! cargo run --example font_sheet -- <font.ttf> <pixel height> <sheet.png> [chars]
+//! ```
+//!
+//! Glyphs are drawn white on black, 16 to a row, in cells as wide as the font's advance and as
+//! tall as its line height.  The glyph size is printed so it can be passed on to `glyph_masks`.
+//! `chars` default to printable ASCII from space to `~` like `glyphs::ASCII`.
+
+use ab_glyph::{point, Font, FontRef, ScaleFont};
+use image::{GrayImage, Luma};
+use std::{env, fs, process};
+
+const COLUMNS: u32 = 16;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.len() < 3 {
+        eprintln!("Usage: font_sheet <font.ttf> <pixel height> <sheet.png> [chars]");
+        process::exit(1);
+    }
+    let data = fs::read(&args[0]).unwrap_or_else(|e| {
+        eprintln!("Unable to read {}: {}", args[0], e);
+        process::exit(1);
+    });
+    let font = FontRef::try_from_slice(&data).unwrap_or_else(|e| {
+        eprintln!("Unable to parse {}: {}", args[0], e);
+        process::exit(1);
+    });
+    let height = args[1].parse::<f32>().unwrap_or_else(|e| {
+        eprintln!("Invalid pixel height {}: {}", args[1], e);
+        process::exit(1);
+    });
+    let chars = args
+        .get(3)
+        .cloned()
+        .unwrap_or_else(|| (' '..='~').collect());
+
+    let font = font.as_scaled(height);
+    let glyph_width = font.h_advance(font.glyph_id('M')).round() as u32;
+    let glyph_height = font.height().ceil() as u32;
+    let count = chars.chars().count() as u32;
+    let rows = (count + COLUMNS - 1) / COLUMNS;
+    let mut sheet = GrayImage::new(glyph_width * COLUMNS.min(count), glyph_height * rows);
+
+    for (i, ch) in chars.chars().enumerate() {
+        let left = (i as u32 % COLUMNS) * glyph_width;
+        let top = (i as u32 / COLUMNS) * glyph_height;
+        let mut glyph = font.scaled_glyph(ch);
+        glyph.position = point(0., font.ascent());
+        let outline = match font.outline_glyph(glyph) {
+            Some(outline) => outline,
+            // Spaces have no outline
+            None => continue,
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|x, y, coverage| {
+            // Parts of a glyph outside its cell are cut off
+            let x = bounds.min.x as i32 + x as i32;
+            let y = bounds.min.y as i32 + y as i32;
+            if (0..glyph_width as i32).contains(&x) && (0..glyph_height as i32).contains(&y) {
+                let level = (coverage.min(1.) * 255.).round() as u8;
+                sheet.put_pixel(left + x as u32, top + y as u32, Luma([level]));
+            }
+        });
+    }
+
+    sheet.save(&args[2]).unwrap_or_else(|e| {
+        eprintln!("Unable to save {}: {}", args[2], e);
+        process::exit(1);
+    });
+    println!("{} {}", glyph_width, glyph_height);
+}
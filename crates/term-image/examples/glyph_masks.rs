@@ -0,0 +1,46 @@
+//! Print `AsciiMode::Structural` masks for a font, in the format of `glyphs::ASCII`
+//!
+//! ```text
+//! cargo run --example glyph_masks -- <sheet.png> <glyph width> <glyph height> [chars]
+//! ```
+//!
+//! The sheet holds one glyph per `glyph width` x `glyph height` cell, drawn white on black and
+//! laid out left to right, top to bottom.  `chars` are the characters on the sheet in order, and
+//! default to printable ASCII from space to `~` like `glyphs::ASCII`.
+
+use std::{env, process};
+use term_image::ascii::glyph_masks;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.len() < 3 {
+        eprintln!("Usage: glyph_masks <sheet.png> <glyph width> <glyph height> [chars]");
+        process::exit(1);
+    }
+    let size = |arg: &str| {
+        arg.parse::<u32>().unwrap_or_else(|e| {
+            eprintln!("Invalid glyph size {}: {}", arg, e);
+            process::exit(1);
+        })
+    };
+    let glyph_size = (size(&args[1]), size(&args[2]));
+    let chars = args
+        .get(3)
+        .cloned()
+        .unwrap_or_else(|| (' '..='~').collect());
+
+    let sheet = match image::open(&args[0]) {
+        Ok(sheet) => sheet.into_luma8(),
+        Err(e) => {
+            eprintln!("Unable to open {}: {}", args[0], e);
+            process::exit(1);
+        }
+    };
+
+    let masks = glyph_masks(&sheet, glyph_size, &chars);
+    println!("pub const ASCII: [(char, [u8; 8]); {}] = [", masks.len());
+    for (ch, mask) in masks {
+        println!("    ({:?}, {:?}),", ch, mask);
+    }
+    println!("];");
+}
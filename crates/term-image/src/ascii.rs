@@ -1,54 +1,388 @@
 // TODO: Improve image output quality?
-use super::{premultiply, resize_image, Rgb as TermRgb};
+use super::{flatten, prepare_frame, render_cells, CellBuffer, Rgb as TermRgb, StyledCell};
 use image::{
-    imageops::FilterType, Delay, DynamicImage, Frames, GenericImageView, GrayImage, ImageBuffer,
-    Luma, Rgb,
+    imageops, Delay, DynamicImage, Frames, GenericImageView, GrayImage, Luma, Rgb, RgbImage,
 };
-use itertools::{IntoChunks, Itertools};
+use itertools::IntoChunks;
 use std::borrow::Cow;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cell {
     pub ch: char,
-    pub fg: TermRgb,
+    /// The color of the character, or `None` when rendering in monochrome
+    pub fg: Option<TermRgb>,
+    /// The color behind the character, only computed when `AsciiOptions::background` is set
+    pub bg: Option<TermRgb>,
 }
 
-fn best_char(brightness: u8, font: &[(char, u8)]) -> char {
-    let mut diff = 100;
-    let mut cand = font[0].0;
-    for x in font {
-        if (i16::from(x.1) - i16::from(brightness)).abs() < diff {
-            diff = (i16::from(x.1) - i16::from(brightness)).abs();
-            cand = x.0;
+impl StyledCell for Cell {
+    fn ch(&self) -> char {
+        self.ch
+    }
+
+    fn fg(&self) -> Option<TermRgb> {
+        self.fg
+    }
+
+    fn bg(&self) -> Option<TermRgb> {
+        self.bg
+    }
+}
+
+/// Characters ordered by brightness, used by `AsciiMode::Brightness`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ramp(Vec<(char, u8)>);
+
+impl Ramp {
+    /// Create a ramp from characters ordered darkest to brightest, spaced evenly
+    ///
+    /// The first character is used for black and the last for white, so like
+    /// `Ramp::from_brightness` the ramp always covers the full range. For example: `" .:-=+*#%@"`
+    ///
+    /// Returns `None` for fewer than two characters, which can't show any shading.
+    pub fn new(chars: &str) -> Option<Ramp> {
+        let count = chars.chars().count();
+        if count < 2 {
+            return None;
+        }
+        Some(Ramp(
+            chars
+                .chars()
+                .enumerate()
+                .map(|(i, ch)| (ch, (i * 255 / (count - 1)) as u8))
+                .collect(),
+        ))
+    }
+
+    /// Create a ramp from measured character brightnesses
+    ///
+    /// The brightnesses are normalized so the darkest character is used for black and the
+    /// brightest for white. Characters with the same brightness as an earlier one are dropped.
+    ///
+    /// Returns `None` unless at least two characters have different brightnesses.
+    pub fn from_brightness(chars: &[(char, u8)]) -> Option<Ramp> {
+        let min = chars.iter().map(|(_, b)| *b).min()?;
+        let max = chars.iter().map(|(_, b)| *b).max()?;
+        if min == max {
+            return None;
+        }
+        let range = u32::from(max - min);
+
+        let mut ramp: Vec<(char, u8)> = Vec::new();
+        for (ch, brightness) in chars {
+            let brightness = (u32::from(brightness - min) * 255 / range) as u8;
+            if ramp.iter().all(|(_, b)| *b != brightness) {
+                ramp.push((*ch, brightness));
+            }
+        }
+        Some(Ramp(ramp))
+    }
+
+    /// All printable ASCII characters, measured from a typical monospace font
+    pub fn standard() -> Ramp {
+        Ramp::from_brightness(&FONT).expect("the font table has different brightnesses")
+    }
+
+    /// A 70 character ramp with finer steps than `Ramp::standard`
+    pub fn detailed() -> Ramp {
+        Ramp::new(" .'`^\",:;Il!i><~+_-?][}{1)(|\\/tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$")
+            .expect("more than one character")
+    }
+
+    /// Unicode shade characters, i.e. `░ ▒ ▓ █`
+    pub fn blocks() -> Ramp {
+        Ramp::new(" ░▒▓█").expect("more than one character")
+    }
+
+    /// Only digits, measured from a typical monospace font
+    pub fn digits() -> Ramp {
+        let digits = FONT
+            .iter()
+            .filter(|(ch, _)| ch.is_ascii_digit())
+            .copied()
+            .collect::<Vec<_>>();
+        Ramp::from_brightness(&digits).expect("the digits have different brightnesses")
+    }
+
+    /// Look up a built in ramp by name, i.e. `standard`, `detailed`, `blocks` or `digits`
+    pub fn preset(name: &str) -> Option<Ramp> {
+        match name {
+            "standard" => Some(Ramp::standard()),
+            "detailed" => Some(Ramp::detailed()),
+            "blocks" => Some(Ramp::blocks()),
+            "digits" => Some(Ramp::digits()),
+            _ => None,
+        }
+    }
+
+    fn best_char(&self, brightness: u8) -> char {
+        let mut diff = i16::MAX;
+        let mut cand = ' ';
+        for x in &self.0 {
+            if (i16::from(x.1) - i16::from(brightness)).abs() < diff {
+                diff = (i16::from(x.1) - i16::from(brightness)).abs();
+                cand = x.0;
+            }
+        }
+        cand
+    }
+}
+
+impl Default for Ramp {
+    fn default() -> Ramp {
+        Ramp::standard()
+    }
+}
+
+fn best_glyph(grid: &[u8; 8], masks: &[(char, [u8; 8])]) -> char {
+    let mut best_diff = u32::MAX;
+    let mut cand = ' ';
+    for (ch, mask) in masks {
+        let diff = grid
+            .iter()
+            .zip(mask.iter())
+            .map(|(l, r)| (i32::from(*l) - i32::from(*r)).pow(2) as u32)
+            .sum();
+        if diff < best_diff {
+            best_diff = diff;
+            cand = *ch;
         }
     }
     cand
 }
 
-fn process_at(
-    x: u32,
-    y: u32,
-    mono: &ImageBuffer<Luma<u8>, Vec<u8>>,
-    img: &DynamicImage,
-    background_color: image::Rgb<u8>,
+fn average(colors: impl Iterator<Item = (Rgb<u8>, u32)>) -> TermRgb {
+    let mut total = 0;
+    let mut color = [0u32; 3];
+    for (p, weight) in colors {
+        total += weight;
+        for i in 0..3 {
+            color[i] += u32::from(p[i]) * weight;
+        }
+    }
+    for c in &mut color {
+        *c = c.checked_div(total).unwrap_or(*c);
+    }
+    TermRgb((color[0] as u8, color[1] as u8, color[2] as u8))
+}
+
+// `sub_ink_img` is the brightness of the cell, inverted if `AsciiOptions::invert` is set,
+// so the character is always drawn where the ink is high
+fn process_cell(
+    sub_img: &impl GenericImageView<Pixel = Rgb<u8>>,
+    sub_ink_img: &impl GenericImageView<Pixel = Luma<u8>>,
+    options: &AsciiOptions,
 ) -> Cell {
-    let mono_pixel = mono.get_pixel(x, y);
-    let char = best_char(mono_pixel[0], &FONT);
-    let pixel = img.get_pixel(x, y);
-    let pixel = premultiply(pixel, background_color);
-    Cell {
-        ch: char,
-        fg: TermRgb((pixel[0], pixel[1], pixel[2])),
+    // Cells are at most 2x4 pixels, see `AsciiOptions::cell_size`
+    let mut ink = [0; 8];
+    let mut len = 0;
+    for ((_, _, p), l) in sub_ink_img.pixels().zip(ink.iter_mut()) {
+        *l = p[0];
+        len += 1;
+    }
+    let ink = &ink[..len];
+    let min = ink.iter().copied().min().unwrap_or(0);
+    let max = ink.iter().copied().max().unwrap_or(0);
+    let split_value = min + (max - min) / 2;
+    let background = options.has_background();
+
+    let ch = match &options.mode {
+        // The background holds the dark half, so the character shows how much of the cell is light
+        AsciiMode::Brightness(ramp) if background => {
+            let count = ink.iter().filter(|l| **l > split_value).count();
+            ramp.best_char((count * 255 / ink.len()) as u8)
+        }
+        AsciiMode::Brightness(ramp) => {
+            let sum = ink.iter().map(|l| u32::from(*l)).sum::<u32>();
+            ramp.best_char((sum / ink.len() as u32) as u8)
+        }
+        AsciiMode::Structural(masks) => {
+            let mut grid = [0; 8];
+            for (l, g) in ink.iter().zip(grid.iter_mut()) {
+                *g = if background {
+                    // Only the shape matters, the brightness is carried by the colors
+                    (u32::from(l - min) * 255 / u32::from(max - min).max(1)) as u8
+                } else {
+                    *l
+                };
+            }
+            best_glyph(&grid, masks)
+        }
+    };
+
+    if options.monochrome {
+        return Cell {
+            ch,
+            fg: None,
+            bg: None,
+        };
+    }
+
+    let pixels = sub_img.pixels().map(|(_, _, p)| p).zip(ink.iter().copied());
+    if background {
+        let fg = average(pixels.clone().map(|(p, l)| (p, u32::from(l > split_value))));
+        let bg = average(pixels.map(|(p, l)| (p, u32::from(l <= split_value))));
+        // A flat cell only has a background
+        let fg = if max > split_value { fg } else { bg };
+        Cell {
+            ch,
+            fg: Some(fg),
+            bg: Some(bg),
+        }
+    } else if ink.iter().any(|l| *l != 0) {
+        // Weight the color by ink, because that is where the character is drawn
+        Cell {
+            ch,
+            fg: Some(average(pixels.map(|(p, l)| (p, u32::from(l))))),
+            bg: None,
+        }
+    } else {
+        Cell {
+            ch,
+            fg: Some(average(pixels.map(|(p, _)| (p, 1)))),
+            bg: None,
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// The brightness of an image, inverted if characters should be drawn where the image is dark
+fn ink(img: &RgbImage, invert: bool) -> GrayImage {
+    let mut ink = imageops::grayscale(img);
+    if invert {
+        imageops::invert(&mut ink);
+    }
+    ink
+}
+
+// x and y are cell coordinates, not pixel coordinates
+fn process_at(x: u32, y: u32, ink: &GrayImage, img: &RgbImage, options: &AsciiOptions) -> Cell {
+    let (cell_width, cell_height) = options.cell_size();
+    let sub_img = img.view(x * cell_width, y * cell_height, cell_width, cell_height);
+    let sub_ink_img = ink.view(x * cell_width, y * cell_height, cell_width, cell_height);
+    process_cell(&*sub_img, &*sub_ink_img, options)
+}
+
+/// Generate glyph masks for `AsciiMode::Structural` from a bitmap font
+///
+/// `font` is a sheet of `glyph_size` glyphs, drawn light on a dark background and laid out left
+/// to right, top to bottom in the same order as `chars`.  Regions of glyphs smaller than 2x4
+/// pixels share pixels, and empty glyphs have blank masks.  See `examples/glyph_masks.rs` to print
+/// masks for a font.
+pub fn glyph_masks(font: &GrayImage, glyph_size: (u32, u32), chars: &str) -> Vec<(char, [u8; 8])> {
+    let (glyph_width, glyph_height) = glyph_size;
+    if glyph_width == 0 || glyph_height == 0 {
+        return Vec::new();
+    }
+    let columns = (font.width() / glyph_width).max(1);
+
+    let mut masks = Vec::new();
+    for (i, ch) in chars.chars().enumerate() {
+        let glyph_x = (i as u32 % columns) * glyph_width;
+        let glyph_y = (i as u32 / columns) * glyph_height;
+        if glyph_x + glyph_width > font.width() || glyph_y + glyph_height > font.height() {
+            break;
+        }
+
+        let glyph = font.view(glyph_x, glyph_y, glyph_width, glyph_height);
+        let mut mask = [0u8; 8];
+        for (region, level) in mask.iter_mut().enumerate() {
+            let (region_x, region_y) = (region as u32 % 2, region as u32 / 2);
+            // Every region covers at least one pixel, even in glyphs smaller than the grid
+            let x0 = (region_x * glyph_width / 2).min(glyph_width - 1);
+            let x1 = ((region_x + 1) * glyph_width / 2).max(x0 + 1);
+            let y0 = (region_y * glyph_height / 4).min(glyph_height - 1);
+            let y1 = ((region_y + 1) * glyph_height / 4).max(y0 + 1);
+
+            let mut sum = 0;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    sum += u32::from(glyph.get_pixel(x, y)[0]);
+                }
+            }
+            *level = (sum / ((x1 - x0) * (y1 - y0))) as u8;
+        }
+        masks.push((ch, mask));
+    }
+
+    // Stretch the coverage so the densest region of any glyph is fully bright
+    let max = masks
+        .iter()
+        .flat_map(|(_, mask)| mask.iter().copied())
+        .max()
+        .unwrap_or(0);
+    if max != 0 {
+        for (_, mask) in &mut masks {
+            for level in mask.iter_mut() {
+                *level = (u32::from(*level) * 255 / u32::from(max)) as u8;
+            }
+        }
+    }
+    masks
+}
+
+/// How characters are chosen for each cell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsciiMode {
+    /// Choose each character from the brightness of a single pixel, or from how much of the cell
+    /// is light when `AsciiOptions::background` is set
+    Brightness(Ramp),
+    /// Match the shape of a 2x4 pixel grid against the coverage of each glyph, which keeps edges
+    /// and lines
+    ///
+    /// Each mask is the coverage of a glyph in 8 regions, left to right and top to bottom.
+    /// Use `glyphs::ASCII`, or generate masks from a font with `glyph_masks`
+    Structural(Cow<'static, [(char, [u8; 8])]>),
+}
+
+impl AsciiMode {
+    /// The number of pixels sampled for each cell
+    const fn cell_size(&self) -> (u32, u32) {
+        match self {
+            AsciiMode::Brightness(_) => (1, 1),
+            AsciiMode::Structural(_) => (2, 4),
+        }
+    }
+}
+
+impl Default for AsciiMode {
+    fn default() -> AsciiMode {
+        AsciiMode::Brightness(Ramp::default())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct AsciiOptions {
     pub size: (u16, u16),
     /// The color to use when premultiply alpha channels.
     ///
     /// This should be the color of whatever background the text will be displayed on
     pub background_color: Rgb<u8>,
+    pub mode: AsciiMode,
+    /// Whether or not to color the background of each cell with the dark half of the cell,
+    /// similar to the block renderer
+    pub background: bool,
+    /// Draw characters where the image is dark instead of where it is light, for terminals with a
+    /// light background
+    pub invert: bool,
+    /// Only output characters, without any colors
+    ///
+    /// Takes precedence over `background`
+    pub monochrome: bool,
+}
+
+impl AsciiOptions {
+    fn has_background(&self) -> bool {
+        self.background && !self.monochrome
+    }
+
+    /// The number of pixels sampled for each cell
+    pub fn cell_size(&self) -> (u32, u32) {
+        if self.has_background() {
+            (2, 4)
+        } else {
+            self.mode.cell_size()
+        }
+    }
 }
 
 /// Render an image using only ASCII characters
@@ -56,106 +390,81 @@ pub struct AsciiOptions {
 pub struct Ascii;
 
 impl Ascii {
+    /// Render animated image without resizing
     pub fn animated_exact<'a>(
         options: &AsciiOptions,
         frames: Frames<'a>,
-    ) -> impl Iterator<Item = (Delay, IntoChunks<CellIter<'a>>)> + 'a {
-        let options = *options;
+    ) -> impl Iterator<Item = (Delay, IntoChunks<CellIter>)> + 'a {
+        let options = options.clone();
         frames.flatten().map(move |frame| {
             let delay = frame.delay();
             let img = DynamicImage::ImageRgba8(frame.into_buffer());
 
-            (delay, Self::img_exact(&options, Cow::Owned(img)))
+            (delay, Self::img_exact(&options, &img))
         })
     }
 
+    /// Render animated image, resizing to fit
     pub fn animated<'a>(
         options: &AsciiOptions,
         frames: Frames<'a>,
-    ) -> impl Iterator<Item = (Delay, IntoChunks<CellIter<'a>>)> + 'a {
-        let options = *options;
+    ) -> impl Iterator<Item = (Delay, IntoChunks<CellIter>)> + 'a {
+        let options = options.clone();
         frames.flatten().map(move |frame| {
             let delay = frame.delay();
             let img = DynamicImage::ImageRgba8(frame.into_buffer());
 
-            let img = resize_image(&img, (1, 1), options.size);
-
             (delay, Self::img(&options, &img))
         })
     }
 
-    pub fn img_exact<'a>(
-        options: &AsciiOptions,
-        img: Cow<'a, DynamicImage>,
-    ) -> IntoChunks<CellIter<'a>> {
-        let mono = img.to_luma8();
-
-        let width = mono.width();
-        CellIter {
-            options: *options,
-            img,
-            mono,
-            i: 0,
-        }
-        .into_iter()
-        .chunks(width as usize)
+    /// Render image without resizing
+    ///
+    /// Each cell covers one pixel, or a 2x4 grid in `AsciiMode::Structural` or with a background
+    pub fn img_exact(options: &AsciiOptions, img: &DynamicImage) -> IntoChunks<CellIter> {
+        Self::buffer_exact(options, img).into_chunks()
     }
 
-    pub fn img<'a>(options: &AsciiOptions, img: &DynamicImage) -> IntoChunks<CellIter<'a>> {
-        // Keep aspect ratio, fit in terminal
-        let img = img.resize(
-            u32::from(options.size.0) / 2,
-            u32::from(options.size.1),
-            FilterType::Nearest,
-        );
-
-        // Stretch out horizontally so it looks decent
-        let img = img.resize_exact(img.width() * 2, img.height(), FilterType::Nearest);
-
-        let mono = img.to_luma8();
-
-        let width = mono.width();
-
-        CellIter {
-            options: *options,
-            img: Cow::Owned(img),
-            mono,
-            i: 0,
-        }
-        .into_iter()
-        .chunks(width as usize)
+    /// Render image, resizing to fit
+    pub fn img(options: &AsciiOptions, img: &DynamicImage) -> IntoChunks<CellIter> {
+        Self::buffer(options, img).into_chunks()
     }
-}
 
-pub struct CellIter<'a> {
-    options: AsciiOptions,
-    img: Cow<'a, DynamicImage>,
-    mono: GrayImage,
-    i: u32,
-}
-
-impl<'a> Iterator for CellIter<'a> {
-    type Item = Cell;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.i >= (self.img.width() * self.img.height()) {
-            return None;
-        }
+    /// Render image into a buffer without resizing
+    ///
+    /// Rows are rendered in parallel with the `rayon` feature
+    pub fn buffer_exact(options: &AsciiOptions, img: &DynamicImage) -> CellBuffer<Cell> {
+        Self::render(options, &flatten(img, options.background_color))
+    }
 
-        let cell = process_at(
-            self.i % self.img.width(),
-            self.i / self.img.width(),
-            &self.mono,
-            &self.img,
-            self.options.background_color,
+    /// Render image into a buffer, resizing to fit
+    ///
+    /// Rows are rendered in parallel with the `rayon` feature
+    pub fn buffer(options: &AsciiOptions, img: &DynamicImage) -> CellBuffer<Cell> {
+        let img = prepare_frame(
+            img,
+            options.cell_size(),
+            options.size,
+            options.background_color,
         );
+        Self::render(options, &img)
+    }
 
-        self.i += 1;
-        Some(cell)
+    fn render(options: &AsciiOptions, img: &RgbImage) -> CellBuffer<Cell> {
+        let ink = ink(img, options.invert);
+        let (cell_width, cell_height) = options.cell_size();
+        render_cells(
+            img.width() / cell_width,
+            img.height() / cell_height,
+            |x, y| process_at(x, y, &ink, img, options),
+        )
     }
 }
 
-// TODO: Remove dupes?
+/// Iterator over the cells of a rendered image, row by row
+pub type CellIter = crate::CellIter<Cell>;
+
+// Duplicates are removed by `Ramp::from_brightness`
 const FONT: [(char, u8); 94] = [
     // (' ', 0),
     ('`', 16),
@@ -253,3 +562,291 @@ const FONT: [(char, u8); 94] = [
     ('B', 218),
     ('M', 218),
 ];
+
+/// This module contains the coverage masks used by `AsciiMode::Structural`
+///
+/// Each mask is the coverage of a glyph in a 2x4 grid, left to right and top to bottom, scaled so
+/// the densest region of any glyph is 255.
+///
+/// For example, `=` is `[0, 0, 111, 109, 111, 111, 0, 0]`: the middle two rows are half covered.
+///
+/// The bundled masks were measured from DejaVu Sans Mono at 48 pixels, which draws 25x48 glyphs:
+///
+/// ```text
+/// cargo run --example font_sheet -- DejaVuSansMono.ttf 48 sheet.png
+/// cargo run --example glyph_masks -- sheet.png 25 48
+/// ```
+pub mod glyphs {
+    pub const ASCII: [(char, [u8; 8]); 95] = [
+        (' ', [0, 0, 0, 0, 0, 0, 0, 0]),
+        ('!', [17, 26, 59, 86, 32, 48, 10, 16]),
+        ('"', [41, 37, 80, 75, 0, 0, 0, 0]),
+        ('#', [17, 43, 179, 204, 208, 177, 25, 17]),
+        ('$', [1, 28, 175, 129, 66, 231, 41, 73]),
+        ('%', [55, 0, 193, 91, 71, 201, 0, 43]),
+        ('&', [61, 52, 179, 16, 167, 235, 52, 64]),
+        ('\'', [16, 23, 30, 44, 0, 0, 0, 0]),
+        ('(', [0, 48, 80, 55, 104, 37, 5, 71]),
+        (')', [50, 1, 28, 104, 12, 122, 66, 14]),
+        ('*', [12, 21, 136, 156, 5, 10, 0, 0]),
+        ('+', [0, 0, 46, 66, 134, 152, 0, 0]),
+        (',', [0, 0, 0, 0, 21, 34, 70, 30]),
+        ('-', [0, 0, 0, 0, 50, 53, 0, 0]),
+        ('.', [0, 0, 0, 0, 25, 32, 14, 17]),
+        ('/', [0, 41, 7, 127, 127, 16, 73, 0]),
+        ('0', [61, 64, 175, 170, 170, 161, 35, 39]),
+        ('1', [68, 41, 21, 132, 32, 152, 46, 66]),
+        ('2', [89, 66, 8, 150, 132, 79, 64, 61]),
+        ('3', [89, 66, 44, 167, 35, 167, 68, 41]),
+        ('4', [0, 64, 102, 159, 145, 208, 0, 26]),
+        ('5', [87, 73, 168, 86, 34, 161, 70, 35]),
+        ('6', [53, 79, 202, 91, 170, 152, 35, 46]),
+        ('7', [98, 98, 0, 138, 77, 73, 30, 0]),
+        ('8', [70, 71, 168, 163, 174, 161, 44, 48]),
+        ('9', [71, 62, 158, 159, 93, 193, 55, 30]),
+        (':', [0, 0, 41, 52, 25, 32, 14, 17]),
+        (';', [0, 0, 41, 52, 21, 34, 70, 30]),
+        ('<', [0, 0, 71, 114, 122, 114, 0, 0]),
+        ('=', [0, 0, 111, 109, 111, 111, 0, 0]),
+        ('>', [0, 0, 114, 75, 114, 122, 0, 0]),
+        ('?', [70, 73, 12, 150, 57, 50, 14, 12]),
+        ('@', [12, 32, 168, 217, 210, 184, 100, 73]),
+        ('A', [30, 37, 140, 131, 197, 190, 28, 26]),
+        ('B', [96, 70, 202, 179, 170, 172, 61, 39]),
+        ('C', [46, 91, 163, 3, 168, 30, 21, 66]),
+        ('D', [102, 44, 154, 156, 174, 163, 64, 17]),
+        ('E', [91, 96, 197, 91, 168, 34, 57, 66]),
+        ('F', [82, 104, 190, 89, 154, 0, 28, 0]),
+        ('G', [53, 84, 163, 34, 168, 177, 28, 61]),
+        ('H', [48, 44, 211, 202, 154, 143, 28, 26]),
+        ('I', [87, 89, 62, 84, 84, 105, 57, 57]),
+        ('J', [48, 75, 0, 141, 41, 150, 70, 28]),
+        ('K', [48, 52, 237, 109, 170, 159, 28, 32]),
+        ('L', [48, 0, 154, 0, 168, 35, 53, 71]),
+        ('M', [71, 66, 251, 235, 161, 158, 26, 25]),
+        ('N', [70, 43, 255, 158, 152, 247, 26, 35]),
+        ('O', [64, 68, 159, 147, 167, 156, 37, 41]),
+        ('P', [89, 77, 168, 183, 181, 37, 28, 0]),
+        ('Q', [64, 68, 159, 147, 167, 156, 37, 104]),
+        ('R', [102, 61, 183, 170, 179, 154, 28, 28]),
+        ('S', [70, 79, 186, 50, 43, 179, 61, 46]),
+        ('T', [122, 120, 61, 86, 61, 86, 10, 16]),
+        ('U', [48, 44, 154, 141, 163, 152, 43, 46]),
+        ('V', [48, 44, 149, 138, 132, 127, 16, 19]),
+        ('W', [46, 43, 197, 193, 226, 208, 30, 26]),
+        ('X', [50, 46, 129, 136, 154, 145, 30, 28]),
+        ('Y', [50, 46, 145, 140, 62, 86, 10, 16]),
+        ('Z', [91, 114, 5, 145, 158, 53, 62, 75]),
+        ('[', [41, 55, 100, 35, 100, 35, 64, 62]),
+        ('\\', [44, 0, 140, 3, 21, 113, 0, 68]),
+        (']', [46, 50, 7, 122, 7, 122, 48, 79]),
+        ('^', [34, 39, 93, 86, 0, 0, 0, 0]),
+        ('_', [0, 0, 0, 0, 0, 0, 61, 59]),
+        ('`', [61, 12, 0, 7, 0, 0, 0, 0]),
+        ('a', [0, 0, 80, 120, 177, 210, 55, 48]),
+        ('b', [59, 0, 192, 125, 172, 145, 48, 48]),
+        ('c', [0, 0, 105, 87, 158, 23, 21, 64]),
+        ('d', [0, 53, 127, 184, 156, 159, 44, 50]),
+        ('e', [0, 0, 120, 122, 208, 125, 34, 62]),
+        ('f', [12, 93, 131, 123, 77, 57, 14, 10]),
+        ('g', [0, 0, 129, 141, 161, 163, 107, 154]),
+        ('h', [59, 0, 186, 127, 140, 131, 25, 23]),
+        ('i', [14, 35, 80, 62, 53, 105, 59, 66]),
+        ('j', [0, 50, 57, 89, 0, 129, 87, 89]),
+        ('k', [61, 0, 152, 100, 184, 141, 26, 28]),
+        ('l', [102, 12, 107, 30, 96, 59, 1, 53]),
+        ('m', [0, 0, 175, 167, 172, 195, 32, 35]),
+        ('n', [0, 0, 141, 127, 140, 131, 25, 23]),
+        ('o', [0, 0, 123, 122, 159, 147, 39, 43]),
+        ('p', [0, 0, 149, 125, 172, 145, 150, 46]),
+        ('q', [0, 0, 123, 145, 156, 159, 43, 149]),
+        ('r', [0, 0, 109, 114, 141, 0, 25, 0]),
+        ('s', [0, 0, 120, 71, 91, 158, 55, 43]),
+        ('t', [26, 1, 181, 82, 120, 37, 5, 55]),
+        ('u', [0, 0, 96, 89, 152, 156, 46, 48]),
+        ('v', [0, 0, 98, 91, 136, 131, 16, 19]),
+        ('w', [0, 0, 100, 96, 217, 206, 28, 26]),
+        ('x', [0, 0, 104, 98, 131, 134, 30, 26]),
+        ('y', [0, 0, 100, 93, 129, 132, 123, 41]),
+        ('z', [0, 0, 68, 143, 120, 62, 55, 59]),
+        ('{', [5, 82, 57, 80, 122, 59, 21, 111]),
+        ('|', [21, 32, 50, 73, 50, 73, 50, 73]),
+        ('}', [80, 14, 57, 79, 37, 134, 102, 37]),
+        ('~', [0, 0, 28, 3, 80, 104, 0, 0]),
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramps_cover_the_full_range() {
+        let ramp = Ramp::new(" .:#").unwrap();
+        assert_eq!(ramp.0, [(' ', 0), ('.', 85), (':', 170), ('#', 255)]);
+        assert_eq!(ramp.best_char(0), ' ');
+        assert_eq!(ramp.best_char(100), '.');
+        assert_eq!(ramp.best_char(255), '#');
+
+        let ramp = Ramp::from_brightness(&[('a', 50), ('b', 100), ('c', 150)]).unwrap();
+        assert_eq!(ramp.0, [('a', 0), ('b', 127), ('c', 255)]);
+    }
+
+    #[test]
+    fn measured_ramps_drop_characters_with_the_same_brightness() {
+        let ramp = Ramp::from_brightness(&[('a', 10), ('b', 20), ('c', 10), ('d', 30)]).unwrap();
+        assert_eq!(ramp.0, [('a', 0), ('b', 127), ('d', 255)]);
+        // The font table has duplicates
+        let mut brightnesses = Ramp::standard()
+            .0
+            .iter()
+            .map(|(_, b)| *b)
+            .collect::<Vec<_>>();
+        let count = brightnesses.len();
+        brightnesses.sort_unstable();
+        brightnesses.dedup();
+        assert_eq!(brightnesses.len(), count);
+        assert!(count < FONT.len());
+    }
+
+    #[test]
+    fn ramps_need_two_shades() {
+        assert_eq!(Ramp::new(""), None);
+        assert_eq!(Ramp::new("#"), None);
+        assert_eq!(Ramp::from_brightness(&[]), None);
+        assert_eq!(Ramp::from_brightness(&[('a', 10), ('b', 10)]), None);
+    }
+
+    #[test]
+    fn presets_are_found_by_name() {
+        assert_eq!(Ramp::preset("standard"), Some(Ramp::standard()));
+        assert_eq!(Ramp::preset("detailed"), Some(Ramp::detailed()));
+        assert_eq!(Ramp::preset("blocks"), Some(Ramp::blocks()));
+        assert_eq!(Ramp::preset("digits"), Some(Ramp::digits()));
+        assert_eq!(Ramp::preset("Standard"), None);
+        assert_eq!(Ramp::preset(" .:#"), None);
+        assert!(Ramp::digits().0.iter().all(|(ch, _)| ch.is_ascii_digit()));
+        assert_eq!(Ramp::default(), Ramp::standard());
+    }
+    fn options(mode: AsciiMode) -> AsciiOptions {
+        AsciiOptions {
+            size: (0, 0),
+            background_color: Rgb([0, 0, 0]),
+            mode,
+            background: false,
+            invert: false,
+            monochrome: true,
+        }
+    }
+
+    fn text(buffer: &CellBuffer<Cell>) -> Vec<String> {
+        buffer
+            .rows()
+            .map(|row| row.iter().map(|cell| cell.ch).collect())
+            .collect()
+    }
+
+    // Lit pixels where `pattern` has a `#`, one line per row
+    fn image(pattern: &[&str]) -> DynamicImage {
+        let (width, height) = (pattern[0].len() as u32, pattern.len() as u32);
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            match pattern[y as usize].as_bytes()[x as usize] {
+                b'#' => Rgb([255, 255, 255]),
+                _ => Rgb([0, 0, 0]),
+            }
+        }))
+    }
+
+    #[test]
+    fn solid_and_empty_tiles_match_the_densest_and_blank_glyphs() {
+        let options = options(AsciiMode::Structural(Cow::Borrowed(&glyphs::ASCII)));
+        let img = image(&["##..", "##..", "##..", "##.."]);
+        // `B` covers the most of every region, `@` is darker overall but thin at the edges
+        assert_eq!(text(&Ascii::buffer_exact(&options, &img)), ["B "]);
+
+        let densest = glyphs::ASCII
+            .iter()
+            .min_by_key(|(_, mask)| {
+                mask.iter()
+                    .map(|l| (255 - u32::from(*l)).pow(2))
+                    .sum::<u32>()
+            })
+            .unwrap();
+        assert_eq!(densest.0, 'B');
+    }
+
+    #[test]
+    fn glyph_masks_are_measured_in_regions_and_stretched() {
+        // Two 2x4 glyphs: a half-lit left column, and nothing
+        let sheet = GrayImage::from_fn(4, 4, |x, _| Luma([if x == 0 { 128 } else { 0 }]));
+        assert_eq!(
+            glyph_masks(&sheet, (2, 4), "|. "),
+            [('|', [255, 0, 255, 0, 255, 0, 255, 0]), ('.', [0; 8])]
+        );
+        assert_eq!(glyph_masks(&sheet, (0, 4), "|"), []);
+    }
+    fn gray(levels: &[u8]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(levels.len() as u32, 1, |x, _| {
+            Rgb([levels[x as usize]; 3])
+        }))
+    }
+
+    #[test]
+    fn invert_reverses_the_ramp() {
+        let mut options = options(AsciiMode::Brightness(Ramp::new(" .:#").unwrap()));
+        let img = gray(&[0, 85, 170, 255]);
+        assert_eq!(text(&Ascii::buffer_exact(&options, &img)), [" .:#"]);
+        options.invert = true;
+        assert_eq!(text(&Ascii::buffer_exact(&options, &img)), ["#:. "]);
+    }
+
+    #[test]
+    fn monochrome_cells_have_no_colors() {
+        let mut options = options(AsciiMode::default());
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 1, Rgb([200, 40, 0])));
+        let cells = Ascii::buffer_exact(&options, &img);
+        assert!(cells.rows().flatten().all(|cell| cell.fg.is_none()));
+
+        options.monochrome = false;
+        let cells = Ascii::buffer_exact(&options, &img);
+        assert!(cells
+            .rows()
+            .flatten()
+            .all(|cell| cell.fg == Some(TermRgb((200, 40, 0))) && cell.bg.is_none()));
+
+        // Monochrome takes precedence over the background
+        options.monochrome = true;
+        options.background = true;
+        let cells = Ascii::buffer_exact(&options, &img);
+        assert!(cells
+            .rows()
+            .flatten()
+            .all(|cell| cell.fg.is_none() && cell.bg.is_none()));
+    }
+
+    #[test]
+    fn background_holds_the_dark_half_of_the_cell() {
+        let mut options = options(AsciiMode::default());
+        options.monochrome = false;
+        options.background = true;
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(2, 4, |x, _| {
+            if x == 0 {
+                Rgb([250, 250, 200])
+            } else {
+                Rgb([0, 0, 100])
+            }
+        }));
+        let cells = Ascii::buffer_exact(&options, &img);
+        assert_eq!((cells.width(), cells.height()), (1, 1));
+        let cell = cells.rows().flatten().next().unwrap();
+        assert_eq!(cell.fg, Some(TermRgb((250, 250, 200))));
+        assert_eq!(cell.bg, Some(TermRgb((0, 0, 100))));
+
+        // A flat cell is drawn in its background color
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 4, Rgb([0, 0, 100])));
+        let cells = Ascii::buffer_exact(&options, &img);
+        let cell = cells.rows().flatten().next().unwrap();
+        assert_eq!(cell.fg, cell.bg);
+        assert_eq!(cell.bg, Some(TermRgb((0, 0, 100))));
+    }
+}
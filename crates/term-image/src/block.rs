@@ -1,13 +1,13 @@
-use super::{premultiply, resize_image, Rgb as TermRgb};
-use image::{Delay, DynamicImage, Frames, GenericImageView, Rgba};
-use itertools::{IntoChunks, Itertools};
-use std::borrow::Cow;
+use super::{color, flatten, prepare_frame, render_cells, CellBuffer, Rgb as TermRgb, StyledCell};
+use image::{Delay, DynamicImage, Frames, GenericImageView, Rgb, RgbImage};
+use itertools::IntoChunks;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Charset {
     /// Use all "fractional" block characters, most of the ["Box Drawing"](https://en.wikipedia.org/wiki/Box_Drawing_(Unicode_block))
     /// and ["Block Elements"](https://en.wikipedia.org/wiki/Block_Elements#Compact_table) characters,
     /// and "slope characters"
+    #[default]
     All,
     /// Same as `Charset::All`, but without "slopes" because some fonts render them wide, i.e. `◢`, `◣`, `◤`, `◥`.
     NoSlopes,
@@ -31,12 +31,6 @@ impl Charset {
     }
 }
 
-impl Default for Charset {
-    fn default() -> Charset {
-        Charset::All
-    }
-}
-
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cell {
     pub ch: char,
@@ -44,18 +38,64 @@ pub struct Cell {
     pub bg: TermRgb,
 }
 
-fn process_block(
-    sub_img: &impl GenericImageView<Pixel = Rgba<u8>>,
-    bitmaps: &[(u32, char)],
-    blend: bool,
-    bg_premultiply_color: image::Rgb<u8>,
-) -> Cell {
+impl StyledCell for Cell {
+    fn ch(&self) -> char {
+        self.ch
+    }
+
+    fn fg(&self) -> Option<TermRgb> {
+        Some(self.fg)
+    }
+
+    fn bg(&self) -> Option<TermRgb> {
+        Some(self.bg)
+    }
+}
+
+/// How the foreground and background colors of each block are chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorFit {
+    /// Split the block at the midpoint of the color channel with the widest range, and average
+    /// each half in sRGB
+    #[default]
+    Split,
+    /// Cluster the block into two colors with k-means in the [OKLab](https://bottosson.github.io/posts/oklab/)
+    /// color space, and average each cluster in linear light
+    ///
+    /// Slower than `ColorFit::Split`, but follows hue boundaries and doesn't darken edges
+    Perceptual,
+}
+
+/// How the character for each block is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlyphMatch {
+    /// Choose the character whose shape differs from the foreground of the block in the fewest
+    /// pixels
+    #[default]
+    Bitmap,
+    /// Choose the character that leaves the least color error, when drawn with the best colors
+    /// for its shape
+    ///
+    /// Slower than `GlyphMatch::Bitmap`, but better for gradients and blocks with more than two
+    /// colors.  Blending characters are likewise used when they leave less color error.
+    ColorError,
+}
+
+/// The two colors of a block, and which pixels belong to the foreground
+struct Fit {
+    /// One bit per pixel, set for foreground pixels, with the first pixel in the highest bit
+    bits: u32,
+    fg_count: u32,
+    fg: [u8; 3],
+    bg: [u8; 3],
+}
+
+fn fit_split(pixels: &[Rgb<u8>]) -> Fit {
     // Determine the best color
     // First, determine the best color range
     let mut max = [0u8; 3];
     let mut min = [255u8; 3];
-    for (_, _, p) in sub_img.pixels() {
-        let p = premultiply(p, bg_premultiply_color);
+    for p in pixels {
         for i in 0..3 {
             max[i] = max[i].max(p[i]);
             min[i] = min[i].min(p[i]);
@@ -81,37 +121,149 @@ fn process_block(
     let mut bg_color = [0u32; 3];
     let mut bits = 0u32;
 
-    for y in 0..sub_img.height() {
-        for x in 0..sub_img.width() {
-            bits <<= 1;
-            let pixel = sub_img.get_pixel(x, y);
-            let pixel = premultiply(pixel, bg_premultiply_color);
-            if pixel[split_index] > split_value {
-                bits |= 1;
-                fg_count += 1;
-                for i in 0..3 {
-                    fg_color[i] += u32::from(pixel[i]);
-                }
-            } else {
-                bg_count += 1;
-                for i in 0..3 {
-                    bg_color[i] += u32::from(pixel[i]);
-                }
+    for pixel in pixels {
+        bits <<= 1;
+        if pixel[split_index] > split_value {
+            bits |= 1;
+            fg_count += 1;
+            for i in 0..3 {
+                fg_color[i] += u32::from(pixel[i]);
+            }
+        } else {
+            bg_count += 1;
+            for i in 0..3 {
+                bg_color[i] += u32::from(pixel[i]);
             }
         }
     }
 
     // Get the averages
     for i in 0..3 {
-        if fg_count != 0 {
-            fg_color[i] /= fg_count;
+        fg_color[i] = fg_color[i].checked_div(fg_count).unwrap_or(fg_color[i]);
+        bg_color[i] = bg_color[i].checked_div(bg_count).unwrap_or(bg_color[i]);
+    }
+
+    Fit {
+        bits,
+        fg_count,
+        fg: [fg_color[0] as u8, fg_color[1] as u8, fg_color[2] as u8],
+        bg: [bg_color[0] as u8, bg_color[1] as u8, bg_color[2] as u8],
+    }
+}
+
+/// Whether pixel `i` of `len` is set in a bitmap
+fn is_set(bitmap: u32, i: usize, len: usize) -> bool {
+    bitmap & (1 << (len - 1 - i)) != 0
+}
+
+fn mean(colors: impl Iterator<Item = [f32; 3]>) -> Option<[f32; 3]> {
+    let mut count = 0;
+    let mut sum = [0.; 3];
+    for c in colors {
+        count += 1;
+        for i in 0..3 {
+            sum[i] += c[i];
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+    Some([
+        sum[0] / count as f32,
+        sum[1] / count as f32,
+        sum[2] / count as f32,
+    ])
+}
+
+fn fit_perceptual(pixels: &[Rgb<u8>]) -> Fit {
+    const MAX_ITERATIONS: usize = 8;
+
+    let linear = pixels
+        .iter()
+        .map(|p| {
+            [
+                color::srgb_to_linear(p[0]),
+                color::srgb_to_linear(p[1]),
+                color::srgb_to_linear(p[2]),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let lab = linear
+        .iter()
+        .map(|c| color::linear_to_oklab(*c))
+        .collect::<Vec<_>>();
+
+    // Start from the split, which is usually close already
+    let seed = fit_split(pixels).bits;
+    let mut in_fg = (0..pixels.len())
+        .map(|i| is_set(seed, i, pixels.len()))
+        .collect::<Vec<_>>();
+
+    for _ in 0..MAX_ITERATIONS {
+        let cluster = |fg: bool| {
+            mean(
+                lab.iter()
+                    .zip(&in_fg)
+                    .filter(|(_, in_fg)| **in_fg == fg)
+                    .map(|(c, _)| *c),
+            )
+        };
+        let (fg, bg) = match (cluster(true), cluster(false)) {
+            (Some(fg), Some(bg)) => (fg, bg),
+            // The block is a single color
+            _ => break,
+        };
+
+        let next = lab
+            .iter()
+            .map(|c| color::distance(*c, fg) < color::distance(*c, bg))
+            .collect::<Vec<_>>();
+        if next == in_fg {
+            break;
         }
+        in_fg = next;
+    }
 
-        if bg_count != 0 {
-            bg_color[i] /= bg_count;
+    let bits = in_fg.iter().fold(0, |bits, fg| bits << 1 | u32::from(*fg));
+    let (fg, bg) = mask_colors(pixels, bits, ColorFit::Perceptual);
+    Fit {
+        bits,
+        fg_count: in_fg.iter().filter(|fg| **fg).count() as u32,
+        fg,
+        bg,
+    }
+}
+
+/// The average colors inside and outside of a bitmap, averaged the way `color_fit` does
+///
+/// If either side is empty, both colors are the average of the other side
+fn mask_colors(pixels: &[Rgb<u8>], bitmap: u32, color_fit: ColorFit) -> ([u8; 3], [u8; 3]) {
+    let average = |set: bool| {
+        let colors = pixels
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| is_set(bitmap, *i, pixels.len()) == set)
+            .map(|(_, p)| p);
+        match color_fit {
+            ColorFit::Split => {
+                mean(colors.map(|p| p.0.map(f32::from))).map(|c| c.map(|c| (c as u32) as u8))
+            }
+            ColorFit::Perceptual => mean(colors.map(|p| p.0.map(color::srgb_to_linear)))
+                .map(|c| c.map(color::linear_to_srgb)),
         }
+    };
+
+    match (average(true), average(false)) {
+        (Some(fg), Some(bg)) => (fg, bg),
+        (Some(c), None) | (None, Some(c)) => (c, c),
+        (None, None) => ([0; 3], [0; 3]),
     }
+}
 
+/// Find the character whose bitmap differs from `bits` in the fewest pixels
+///
+/// Returns the character, whether it is inverted, and the number of differing pixels
+fn match_bitmap(bits: u32, bitmaps: &[(u32, char)]) -> (char, bool, u32) {
     // A perfect match is 0x0 so start at max
     let mut best_diff = 0xffff_ffffu32;
     let mut best_char = ' ';
@@ -135,38 +287,151 @@ fn process_block(
         }
     }
 
-    if blend {
-        // If the bitmap does not fit "well", use a gradient
-        if best_diff > 10 {
-            invert = false;
-            best_char = [' ', '\u{2591}', '\u{2592}', '\u{2593}', '\u{2588}']
-                [4.min(fg_count as usize * 5 / 32)];
+    (best_char, invert, best_diff)
+}
+
+/// Find the bitmap that leaves the least color error when its inside and outside are each
+/// drawn with their average color
+///
+/// The error of each split is the sum of squared distances from the averages, measured in sRGB
+/// or OKLab depending on `color_fit`.  Returns the bitmap, its character, and its error.
+fn match_color_error(
+    pixels: &[Rgb<u8>],
+    bitmaps: &[(u32, char)],
+    color_fit: ColorFit,
+) -> (u32, char, f32) {
+    let colors = pixels
+        .iter()
+        .map(|p| error_space(p.0, color_fit))
+        .collect::<Vec<_>>();
+
+    let norm = |c: [f32; 3]| c[0] * c[0] + c[1] * c[1] + c[2] * c[2];
+    let mut total = [0.; 3];
+    let mut total_norm = 0.;
+    for c in &colors {
+        for i in 0..3 {
+            total[i] += c[i];
+        }
+        total_norm += norm(*c);
+    }
+
+    let mut best = (0, ' ', f32::MAX);
+    for (bitmap, ch) in bitmaps.iter() {
+        let mut inside = [0.; 3];
+        let mut count = 0;
+        for (i, c) in colors.iter().enumerate() {
+            if is_set(*bitmap, i, colors.len()) {
+                count += 1;
+                for j in 0..3 {
+                    inside[j] += c[j];
+                }
+            }
+        }
+        let outside = [
+            total[0] - inside[0],
+            total[1] - inside[1],
+            total[2] - inside[2],
+        ];
+
+        // The squared error around the averages, without computing the averages
+        let mut error = total_norm;
+        if count != 0 {
+            error -= norm(inside) / count as f32;
+        }
+        if count != colors.len() {
+            error -= norm(outside) / (colors.len() - count) as f32;
+        }
+
+        if error < best.2 {
+            best = (*bitmap, *ch, error);
         }
     }
+    best
+}
 
-    // If best map is inverted, swap the colors
-    if invert {
-        std::mem::swap(&mut fg_color, &mut bg_color);
+/// A color in the space `color_fit` measures color error in
+fn error_space(color: [u8; 3], color_fit: ColorFit) -> [f32; 3] {
+    match color_fit {
+        ColorFit::Split => color.map(f32::from),
+        ColorFit::Perceptual => color::linear_to_oklab(color.map(color::srgb_to_linear)),
     }
+}
+
+/// The blending character closest to the share of foreground pixels in a block, and the share
+/// of the cell it covers
+fn shade(fg_count: u32) -> (char, f32) {
+    const SHADES: [char; 5] = [' ', '\u{2591}', '\u{2592}', '\u{2593}', '\u{2588}'];
+    let i = 4.min(fg_count as usize * 5 / 32);
+    (SHADES[i], i as f32 / 4.)
+}
+
+/// The color error of drawing a block as the even mix of `fg` covering `coverage` of `bg`, which
+/// is how a blending character looks, mixed the way `color_fit` averages
+fn shade_error(
+    pixels: &[Rgb<u8>],
+    (fg, bg): ([u8; 3], [u8; 3]),
+    coverage: f32,
+    color_fit: ColorFit,
+) -> f32 {
+    let mix = |fg: f32, bg: f32| fg * coverage + bg * (1. - coverage);
+    let mixed = match color_fit {
+        ColorFit::Split => [0, 1, 2].map(|i| mix(f32::from(fg[i]), f32::from(bg[i]))),
+        ColorFit::Perceptual => color::linear_to_oklab(
+            [0, 1, 2].map(|i| mix(color::srgb_to_linear(fg[i]), color::srgb_to_linear(bg[i]))),
+        ),
+    };
+    pixels
+        .iter()
+        .map(|p| color::distance(error_space(p.0, color_fit), mixed))
+        .sum()
+}
+
+fn process_block(sub_img: &impl GenericImageView<Pixel = Rgb<u8>>, options: &BlockOptions) -> Cell {
+    let pixels = sub_img.pixels().map(|(_, _, p)| p).collect::<Vec<_>>();
+    let fit = match options.color_fit {
+        ColorFit::Split => fit_split(&pixels),
+        ColorFit::Perceptual => fit_perceptual(&pixels),
+    };
+
+    let bitmaps = options.char_set.bitmap();
+    let (shade, coverage) = shade(fit.fg_count);
+    let (ch, fg, bg) = match options.glyph_match {
+        GlyphMatch::Bitmap => {
+            let (ch, invert, diff) = match_bitmap(fit.bits, bitmaps);
+            if options.blend && diff > options.blend_threshold {
+                // The bitmap does not fit "well", so use a gradient
+                (shade, fit.fg, fit.bg)
+            } else if invert {
+                // The best map is inverted, so swap the colors
+                (ch, fit.bg, fit.fg)
+            } else {
+                (ch, fit.fg, fit.bg)
+            }
+        }
+        GlyphMatch::ColorError => {
+            let (bitmap, ch, error) = match_color_error(&pixels, bitmaps, options.color_fit);
+            let colors = (fit.fg, fit.bg);
+            // Use a gradient when it is closer to the block than the best character
+            if options.blend && shade_error(&pixels, colors, coverage, options.color_fit) < error {
+                (shade, fit.fg, fit.bg)
+            } else {
+                let (fg, bg) = mask_colors(&pixels, bitmap, options.color_fit);
+                (ch, fg, bg)
+            }
+        }
+    };
 
     Cell {
-        ch: best_char,
-        fg: TermRgb((fg_color[0] as u8, fg_color[1] as u8, fg_color[2] as u8)),
-        bg: TermRgb((bg_color[0] as u8, bg_color[1] as u8, bg_color[2] as u8)),
+        ch,
+        fg: TermRgb((fg[0], fg[1], fg[2])),
+        bg: TermRgb((bg[0], bg[1], bg[2])),
     }
 }
 
 // x and y are block coordinates, not pixel coordinates
-fn process_at(
-    x: u32,
-    y: u32,
-    img: &image::DynamicImage,
-    bitmap: &[(u32, char)],
-    blend: bool,
-    background_color: image::Rgb<u8>,
-) -> Cell {
+fn process_at(x: u32, y: u32, img: &RgbImage, options: &BlockOptions) -> Cell {
     let sub_img = img.view(x * 4, y * 8, 4, 8);
-    process_block(&*sub_img, bitmap, blend, background_color)
+    process_block(&*sub_img, options)
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -174,11 +439,19 @@ pub struct BlockOptions {
     pub char_set: Charset,
     /// Whether or not to use "blending characters", i.e. `░ ▒ ▓ █`
     pub blend: bool,
+    /// How many of the 32 pixels in a block may differ from the shape of the chosen character
+    /// before a blending character is used instead (10 is a good default)
+    ///
+    /// Only used with `GlyphMatch::Bitmap`, `GlyphMatch::ColorError` compares the color error
+    /// instead
+    pub blend_threshold: u32,
     /// The color to use when premultiply alpha channels.
     ///
     /// This should be the color of whatever background the text will be displayed on
     pub background_color: image::Rgb<u8>,
     pub size: (u16, u16),
+    pub color_fit: ColorFit,
+    pub glyph_match: GlyphMatch,
 }
 
 /// Render an image using [Unicode box-drawing characters](https://en.wikipedia.org/wiki/Box-drawing_character)
@@ -193,13 +466,13 @@ impl Block {
     pub fn animated_exact<'a>(
         options: &BlockOptions,
         frames: Frames<'a>,
-    ) -> impl Iterator<Item = (Delay, IntoChunks<CellIter<'a>>)> + 'a {
+    ) -> impl Iterator<Item = (Delay, IntoChunks<CellIter>)> + 'a {
         let options = *options;
         frames.flatten().map(move |frame| {
             let delay = frame.delay();
             let img = DynamicImage::ImageRgba8(frame.into_buffer());
 
-            (delay, Self::img_exact(&options, Cow::Owned(img)))
+            (delay, Self::img_exact(&options, &img))
         })
     }
 
@@ -207,78 +480,51 @@ impl Block {
     pub fn animated<'a>(
         options: &BlockOptions,
         frames: Frames<'a>,
-    ) -> impl Iterator<Item = (Delay, IntoChunks<CellIter<'a>>)> + 'a {
+    ) -> impl Iterator<Item = (Delay, IntoChunks<CellIter>)> + 'a {
         let options = *options;
         frames.flatten().map(move |frame| {
             let delay = frame.delay();
             let img = DynamicImage::ImageRgba8(frame.into_buffer());
 
-            let img = resize_image(&img, (4, 8), options.size);
-
-            (delay, Self::img_exact(&options, Cow::Owned(img)))
+            (delay, Self::img(&options, &img))
         })
     }
 
     /// Render image without resizing
-    pub fn img_exact<'a>(
-        options: &BlockOptions,
-        img: Cow<'a, DynamicImage>,
-    ) -> IntoChunks<CellIter<'a>> {
-        let block_width = img.width() / 4;
-        CellIter {
-            img,
-            options: *options,
-            i: 0,
-        }
-        .into_iter()
-        .chunks((block_width) as usize)
+    pub fn img_exact(options: &BlockOptions, img: &DynamicImage) -> IntoChunks<CellIter> {
+        Self::buffer_exact(options, img).into_chunks()
     }
 
     /// Render image, resizing to nearest cell width
-    pub fn img<'a>(options: &BlockOptions, img: &DynamicImage) -> IntoChunks<CellIter<'a>> {
-        let img = resize_image(img, (4, 8), options.size);
-        let block_width = img.width() / 4;
-        CellIter {
-            img: Cow::Owned(img),
-            options: *options,
-            i: 0,
-        }
-        .into_iter()
-        .chunks((block_width) as usize)
+    pub fn img(options: &BlockOptions, img: &DynamicImage) -> IntoChunks<CellIter> {
+        Self::buffer(options, img).into_chunks()
     }
-}
-
-pub struct CellIter<'a> {
-    options: BlockOptions,
-    img: Cow<'a, DynamicImage>,
-    i: u32,
-}
-
-impl<'a> Iterator for CellIter<'a> {
-    type Item = Cell;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let block_width = self.img.width() / 4;
-        let block_height = self.img.height() / 8;
-
-        if self.i >= (block_width * block_height) {
-            return None;
-        }
+    /// Render image into a buffer without resizing
+    ///
+    /// Rows are rendered in parallel with the `rayon` feature
+    pub fn buffer_exact(options: &BlockOptions, img: &DynamicImage) -> CellBuffer<Cell> {
+        Self::render(options, &flatten(img, options.background_color))
+    }
 
-        let cell = process_at(
-            self.i % block_width,
-            self.i / block_width,
-            &self.img,
-            self.options.char_set.bitmap(),
-            self.options.blend,
-            self.options.background_color,
-        );
+    /// Render image into a buffer, resizing to nearest cell width
+    ///
+    /// Rows are rendered in parallel with the `rayon` feature
+    pub fn buffer(options: &BlockOptions, img: &DynamicImage) -> CellBuffer<Cell> {
+        let img = prepare_frame(img, (4, 8), options.size, options.background_color);
+        Self::render(options, &img)
+    }
 
-        self.i += 1;
-        Some(cell)
+    fn render(options: &BlockOptions, img: &RgbImage) -> CellBuffer<Cell> {
+        render_cells(img.width() / 4, img.height() / 8, |x, y| {
+            process_at(x, y, img, options)
+        })
     }
 }
 
+/// Iterator over the cells of a rendered image, row by row
+pub type CellIter = crate::CellIter<Cell>;
+
 /// This module contains the bitmaps for each character in a "charset"
 ///
 /// The bitmaps represent the "dark" section of each character.
@@ -431,3 +677,149 @@ pub mod bitmaps {
         (0x000f7310, '\u{25e5}'),
     ];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raster;
+
+    /// Generated images that are a whole number of blocks, so they render without resizing
+    fn corpus() -> Vec<(&'static str, RgbImage)> {
+        vec![
+            (
+                "horizontal gradient",
+                RgbImage::from_fn(64, 64, |x, _| Rgb([(x * 4) as u8, 64, 255 - (x * 4) as u8])),
+            ),
+            (
+                "diagonal gradient",
+                RgbImage::from_fn(64, 64, |x, y| {
+                    Rgb([(x * 4) as u8, (y * 4) as u8, ((x + y) * 2) as u8])
+                }),
+            ),
+            (
+                "hue edges",
+                RgbImage::from_fn(64, 64, |x, y| match (x * 3 + y) / 37 % 3 {
+                    0 => Rgb([220, 40, 40]),
+                    1 => Rgb([40, 200, 60]),
+                    _ => Rgb([50, 60, 230]),
+                }),
+            ),
+            (
+                "circle",
+                RgbImage::from_fn(64, 64, |x, y| {
+                    let (dx, dy) = (x as i32 - 30, y as i32 - 34);
+                    if dx * dx + dy * dy < 20 * 20 {
+                        Rgb([250, 200, 30])
+                    } else {
+                        Rgb([20, 30, 90])
+                    }
+                }),
+            ),
+        ]
+    }
+
+    fn options(color_fit: ColorFit, glyph_match: GlyphMatch) -> BlockOptions {
+        BlockOptions {
+            char_set: Charset::All,
+            blend: false,
+            blend_threshold: 10,
+            background_color: Rgb([0, 0, 0]),
+            size: (16, 8),
+            color_fit,
+            glyph_match,
+        }
+    }
+
+    fn render(options: &BlockOptions, img: &RgbImage) -> RgbImage {
+        raster::block(&Block::buffer_exact(
+            options,
+            &DynamicImage::ImageRgb8(img.clone()),
+        ))
+    }
+
+    /// Error between the colors of each block of two images as seen from a distance, i.e. mixed
+    /// in linear light, summed as squared OKLab distances
+    fn block_error(a: &RgbImage, b: &RgbImage) -> f64 {
+        let seen = |img: &RgbImage, x: u32, y: u32| {
+            let block = img.view(x * 4, y * 8, 4, 8);
+            let pixels = block.pixels().map(|(_, _, p)| p);
+            color::linear_to_oklab(mean(pixels.map(|p| p.0.map(color::srgb_to_linear))).unwrap())
+        };
+        let mut error = 0.;
+        for y in 0..a.height() / 8 {
+            for x in 0..a.width() / 4 {
+                error += f64::from(color::distance(seen(a, x, y), seen(b, x, y)));
+            }
+        }
+        error
+    }
+
+    #[test]
+    fn perceptual_fit_is_no_worse_than_split() {
+        for glyph_match in [GlyphMatch::Bitmap, GlyphMatch::ColorError] {
+            for (name, img) in corpus() {
+                let split =
+                    block_error(&img, &render(&options(ColorFit::Split, glyph_match), &img));
+                let perceptual = block_error(
+                    &img,
+                    &render(&options(ColorFit::Perceptual, glyph_match), &img),
+                );
+                assert!(
+                    perceptual <= split + 1e-4,
+                    "{} with {:?}: perceptual error {} > split error {}",
+                    name,
+                    glyph_match,
+                    perceptual,
+                    split
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn color_error_blending_is_no_worse_than_characters() {
+        for (name, img) in corpus() {
+            let options = options(ColorFit::Split, GlyphMatch::ColorError);
+            let characters = raster::psnr(&img, &render(&options, &img));
+            let blended = raster::psnr(
+                &img,
+                &render(
+                    &BlockOptions {
+                        blend: true,
+                        ..options
+                    },
+                    &img,
+                ),
+            );
+            assert!(
+                blended >= characters,
+                "{}: blended PSNR {} < PSNR {} without blending",
+                name,
+                blended,
+                characters
+            );
+        }
+    }
+
+    #[test]
+    fn color_error_match_is_no_worse_than_bitmap() {
+        for color_fit in [ColorFit::Split, ColorFit::Perceptual] {
+            for (name, img) in corpus() {
+                let bitmap =
+                    raster::psnr(&img, &render(&options(color_fit, GlyphMatch::Bitmap), &img));
+                let color_error = raster::psnr(
+                    &img,
+                    &render(&options(color_fit, GlyphMatch::ColorError), &img),
+                );
+                assert!(
+                    color_error >= bitmap,
+                    "{} with {:?}: color error PSNR {} < bitmap PSNR {}",
+                    name,
+                    color_fit,
+                    color_error,
+                    bitmap
+                );
+            }
+        }
+    }
+}
@@ -1,15 +1,33 @@
-use super::{premultiply, resize_image, Rgb as TermRgb};
+use super::{flatten, prepare_frame, render_cells, CellBuffer, Rgb as TermRgb, StyledCell};
 use image::{
-    imageops::colorops::{self, BiLevel},
-    Delay, DynamicImage, GenericImageView, GrayImage, ImageBuffer, Luma, Rgb, Rgba,
+    imageops::{
+        self,
+        colorops::{self, BiLevel},
+    },
+    Delay, DynamicImage, GenericImageView, GrayImage, Luma, Rgb, RgbImage,
 };
-use itertools::{IntoChunks, Itertools};
-use std::borrow::Cow;
+use itertools::IntoChunks;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cell {
     pub ch: char,
     pub fg: TermRgb,
+    /// The color of the unset dots, only computed when `BrailleOptions::background` is set
+    pub bg: Option<TermRgb>,
+}
+
+impl StyledCell for Cell {
+    fn ch(&self) -> char {
+        self.ch
+    }
+
+    fn fg(&self) -> Option<TermRgb> {
+        Some(self.fg)
+    }
+
+    fn bg(&self) -> Option<TermRgb> {
+        self.bg
+    }
 }
 
 fn slice_to_braille(data: &[u8]) -> char {
@@ -22,9 +40,9 @@ fn slice_to_braille(data: &[u8]) -> char {
 }
 
 fn process_cell(
-    sub_img: &impl GenericImageView<Pixel = Rgba<u8>>,
+    sub_img: &impl GenericImageView<Pixel = Rgb<u8>>,
     sub_mono_img: &impl GenericImageView<Pixel = Luma<u8>>,
-    bg_premultiply_color: image::Rgb<u8>,
+    background: bool,
 ) -> Cell {
     let mut data = [0; 8];
     // Map each mono pixel to a single braille dot
@@ -37,7 +55,6 @@ fn process_cell(
     let mut max = [0u8; 3];
     let mut min = [255u8; 3];
     for (_, _, p) in sub_img.pixels() {
-        let p = premultiply(p, bg_premultiply_color);
         for i in 0..3 {
             max[i] = max[i].max(p[i]);
             min[i] = min[i].min(p[i]);
@@ -56,47 +73,71 @@ fn process_cell(
 
     let split_value = min[split_index] + best_split / 2;
 
-    // Then use the median of the range to find the average of the forground
+    // Then use the median of the range to find the average of the forground and background
     let mut fg_count = 0;
+    let mut bg_count = 0;
     let mut fg_color = [0u32; 3];
+    let mut bg_color = [0u32; 3];
 
     for y in 0..sub_img.height() {
         for x in 0..sub_img.width() {
             let pixel = sub_img.get_pixel(x, y);
-            let pixel = premultiply(pixel, bg_premultiply_color);
             if pixel[split_index] > split_value {
                 fg_count += 1;
                 for i in 0..3 {
                     fg_color[i] += u32::from(pixel[i]);
                 }
+            } else {
+                bg_count += 1;
+                for i in 0..3 {
+                    bg_color[i] += u32::from(pixel[i]);
+                }
             }
         }
     }
 
-    // Get the average
-    for fg in &mut fg_color {
-        if fg_count != 0 {
-            *fg /= fg_count;
-        }
+    // Get the averages
+    for i in 0..3 {
+        fg_color[i] = fg_color[i].checked_div(fg_count).unwrap_or(fg_color[i]);
+        bg_color[i] = bg_color[i].checked_div(bg_count).unwrap_or(bg_color[i]);
+    }
+
+    // A flat cell only has one color, so use it for both the dots and the background,
+    // otherwise the dots would be drawn in black
+    if background && fg_count == 0 {
+        fg_color = bg_color;
+    } else if background && bg_count == 0 {
+        bg_color = fg_color;
     }
 
     Cell {
         ch: slice_to_braille(&data),
         fg: TermRgb((fg_color[0] as u8, fg_color[1] as u8, fg_color[2] as u8)),
+        bg: if background {
+            Some(TermRgb((
+                bg_color[0] as u8,
+                bg_color[1] as u8,
+                bg_color[2] as u8,
+            )))
+        } else {
+            None
+        },
     }
 }
 
-fn process_at(
-    x: u32,
-    y: u32,
-    mono: &ImageBuffer<Luma<u8>, Vec<u8>>,
-    img: &DynamicImage,
-    background_color: Rgb<u8>,
-) -> Cell {
+fn process_at(x: u32, y: u32, mono: &GrayImage, img: &RgbImage, background: bool) -> Cell {
     let sub_img = img.view(x * 2, y * 4, 2, 4);
     let sub_mono_img = mono.view(x * 2, y * 4, 2, 4);
 
-    process_cell(&*sub_img, &*sub_mono_img, background_color)
+    process_cell(&*sub_img, &*sub_mono_img, background)
+}
+
+fn dither(img: &RgbImage) -> GrayImage {
+    let mut mono = imageops::grayscale(img);
+    let map = BiLevel;
+
+    colorops::dither(&mut mono, &map);
+    mono
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -106,6 +147,9 @@ pub struct BrailleOptions {
     ///
     /// This should be the color of whatever background the text will be displayed on
     pub background_color: Rgb<u8>,
+    /// Whether or not to color the unset dots of each cell, instead of leaving them as the
+    /// terminal background
+    pub background: bool,
 }
 
 /// Render an image using [Unicode Braille characters](https://en.wikipedia.org/wiki/Braille_Patterns#Block)
@@ -117,13 +161,13 @@ impl Braille {
     pub fn animated_exact<'a>(
         options: &BrailleOptions,
         frames: image::Frames<'a>,
-    ) -> impl Iterator<Item = (Delay, IntoChunks<impl Iterator<Item = Cell> + 'a>)> + 'a {
+    ) -> impl Iterator<Item = (Delay, IntoChunks<CellIter>)> + 'a {
         let options = *options;
         frames.flatten().map(move |frame| {
             let delay = frame.delay();
             let img = DynamicImage::ImageRgba8(frame.into_buffer());
 
-            (delay, Self::img_exact(&options, Cow::Owned(img)))
+            (delay, Self::img_exact(&options, &img))
         })
     }
 
@@ -131,89 +175,48 @@ impl Braille {
     pub fn animated<'a>(
         options: &BrailleOptions,
         frames: image::Frames<'a>,
-    ) -> impl Iterator<Item = (Delay, IntoChunks<impl Iterator<Item = Cell> + 'a>)> + 'a {
+    ) -> impl Iterator<Item = (Delay, IntoChunks<CellIter>)> + 'a {
         let options = *options;
         frames.flatten().map(move |frame| {
             let delay = frame.delay();
             let img = DynamicImage::ImageRgba8(frame.into_buffer());
 
-            let img = resize_image(&img, (4, 8), options.size);
-
             (delay, Self::img(&options, &img))
         })
     }
 
     /// Render image without resizing
-    pub fn img_exact<'a>(
-        options: &BrailleOptions,
-        img: Cow<'a, DynamicImage>,
-    ) -> IntoChunks<CellIter<'a>> {
-        let block_width = img.width() / 2;
-
-        let mut mono = img.to_luma8();
-        let map = BiLevel;
-
-        colorops::dither(&mut mono, &map);
-
-        CellIter {
-            options: *options,
-            img,
-            mono,
-            i: 0,
-        }
-        .chunks(block_width as usize)
+    pub fn img_exact(options: &BrailleOptions, img: &DynamicImage) -> IntoChunks<CellIter> {
+        Self::buffer_exact(options, img).into_chunks()
     }
 
     /// Render image, resizing to nearest cell width
-    pub fn img<'a>(
-        options: &BrailleOptions,
-        img: &DynamicImage,
-    ) -> IntoChunks<impl Iterator<Item = Cell> + 'a> {
-        let img = resize_image(img, (2, 4), options.size);
-        let block_width = img.width() / 2;
-
-        let mut mono = img.to_luma8();
-        let map = BiLevel;
-
-        colorops::dither(&mut mono, &map);
-
-        CellIter {
-            options: *options,
-            img: Cow::Owned(img),
-            mono,
-            i: 0,
-        }
-        .chunks(block_width as usize)
+    pub fn img(options: &BrailleOptions, img: &DynamicImage) -> IntoChunks<CellIter> {
+        Self::buffer(options, img).into_chunks()
     }
-}
-
-pub struct CellIter<'a> {
-    options: BrailleOptions,
-    img: Cow<'a, DynamicImage>,
-    mono: GrayImage,
-    i: u32,
-}
-
-impl<'a> Iterator for CellIter<'a> {
-    type Item = Cell;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let block_width = self.img.width() / 2;
-        let block_height = self.img.height() / 4;
 
-        if self.i >= (block_width * block_height) {
-            return None;
-        }
+    /// Render image into a buffer without resizing
+    ///
+    /// Rows are rendered in parallel with the `rayon` feature
+    pub fn buffer_exact(options: &BrailleOptions, img: &DynamicImage) -> CellBuffer<Cell> {
+        Self::render(options, &flatten(img, options.background_color))
+    }
 
-        let cell = process_at(
-            self.i % block_width,
-            self.i / block_width,
-            &self.mono,
-            &self.img,
-            self.options.background_color,
-        );
+    /// Render image into a buffer, resizing to nearest cell width
+    ///
+    /// Rows are rendered in parallel with the `rayon` feature
+    pub fn buffer(options: &BrailleOptions, img: &DynamicImage) -> CellBuffer<Cell> {
+        let img = prepare_frame(img, (2, 4), options.size, options.background_color);
+        Self::render(options, &img)
+    }
 
-        self.i += 1;
-        Some(cell)
+    fn render(options: &BrailleOptions, img: &RgbImage) -> CellBuffer<Cell> {
+        let mono = dither(img);
+        render_cells(img.width() / 2, img.height() / 4, |x, y| {
+            process_at(x, y, &mono, img, options.background)
+        })
     }
 }
+
+/// Iterator over the cells of a rendered image, row by row
+pub type CellIter = crate::CellIter<Cell>;
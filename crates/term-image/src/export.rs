@@ -0,0 +1,34 @@
+//! Write rendered cells to files
+
+use crate::{CellBuffer, StyledCell};
+use std::io::{self, Write};
+
+/// Write the characters of `buffer` without any colors, one line per row
+///
+/// Trailing spaces are removed from each line.
+pub fn write_text(buffer: &CellBuffer<impl StyledCell>, out: &mut impl Write) -> io::Result<()> {
+    for row in buffer.rows() {
+        let line = row.iter().map(StyledCell::ch).collect::<String>();
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ascii, Rgb};
+
+    #[test]
+    fn text_has_no_colors_or_trailing_spaces() {
+        let cell = |ch| ascii::Cell {
+            ch,
+            fg: Some(Rgb((255, 0, 0))),
+            bg: Some(Rgb((0, 0, 255))),
+        };
+        let buffer = CellBuffer::new(3, "a b  c ".chars().chain(['d', ' ']).map(cell).collect());
+        let mut out = Vec::new();
+        write_text(&buffer, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "a b\n  c\n d\n");
+    }
+}
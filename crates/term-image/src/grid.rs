@@ -0,0 +1,140 @@
+//! Compose several rendered buffers into a captioned grid, like a contact sheet
+
+use crate::CellBuffer;
+
+/// A rendered image with lines of caption shown below it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tile<C> {
+    pub buffer: CellBuffer<C>,
+    pub caption: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridOptions {
+    /// Number of tiles in each row
+    pub columns: usize,
+    /// Width of each tile in cells, wider buffers and captions are cut off
+    pub tile_width: usize,
+    /// Blank cells between tiles, as (columns, rows)
+    pub gap: (usize, usize),
+}
+
+impl GridOptions {
+    /// The number of tiles of `tile_width` that fit in `width` cells, at least one
+    pub fn fit(width: usize, tile_width: usize, gap: (usize, usize)) -> Self {
+        Self {
+            columns: ((width + gap.0) / (tile_width + gap.0).max(1)).max(1),
+            tile_width,
+            gap,
+        }
+    }
+}
+
+/// Lay out `tiles` in rows of `options.columns`, centering each buffer and its caption
+///
+/// `text` creates the cells used for captions and the space around tiles.
+pub fn compose<C: Clone>(
+    options: &GridOptions,
+    tiles: &[Tile<C>],
+    text: impl Fn(char) -> C,
+) -> CellBuffer<C> {
+    let columns = options.columns.max(1).min(tiles.len());
+    let tile_width = options.tile_width;
+    let width = (columns * (tile_width + options.gap.0)).saturating_sub(options.gap.0);
+
+    let mut lines: Vec<Vec<C>> = Vec::new();
+    for (i, row) in tiles.chunks(columns.max(1)).enumerate() {
+        if i > 0 {
+            lines.extend((0..options.gap.1).map(|_| vec![text(' '); width]));
+        }
+
+        let image_height = row.iter().map(|tile| tile.buffer.height()).max();
+        let caption_height = row.iter().map(|tile| tile.caption.len()).max();
+        let top = lines.len();
+        let height = image_height.unwrap_or(0) + caption_height.unwrap_or(0);
+        lines.extend((0..height).map(|_| vec![text(' '); width]));
+
+        for (column, tile) in row.iter().enumerate() {
+            let left = column * (tile_width + options.gap.0);
+            let buffer_width = tile.buffer.width().min(tile_width);
+            let x = left + (tile_width - buffer_width) / 2;
+            for (y, cells) in tile.buffer.rows().enumerate() {
+                lines[top + y][x..x + buffer_width].clone_from_slice(&cells[..buffer_width]);
+            }
+
+            for (y, caption) in tile.caption.iter().enumerate() {
+                let caption = caption.chars().take(tile_width).collect::<Vec<_>>();
+                let x = left + (tile_width - caption.len()) / 2;
+                let line = &mut lines[top + image_height.unwrap_or(0) + y];
+                for (cell, ch) in line[x..].iter_mut().zip(caption) {
+                    *cell = text(ch);
+                }
+            }
+        }
+    }
+
+    CellBuffer::new(width, lines.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(width: usize, height: usize, ch: char, caption: &[&str]) -> Tile<char> {
+        Tile {
+            buffer: CellBuffer::new(width, vec![ch; width * height]),
+            caption: caption.iter().map(|line| line.to_string()).collect(),
+        }
+    }
+
+    fn lines(buffer: &CellBuffer<char>) -> Vec<String> {
+        buffer.rows().map(|row| row.iter().collect()).collect()
+    }
+
+    #[test]
+    fn fit_counts_tiles_and_gaps() {
+        assert_eq!(GridOptions::fit(20, 6, (1, 1)).columns, 3);
+        assert_eq!(GridOptions::fit(20, 5, (0, 1)).columns, 4);
+        // Always at least one, even when a tile doesn't fit
+        assert_eq!(GridOptions::fit(3, 6, (1, 1)).columns, 1);
+        assert_eq!(GridOptions::fit(0, 0, (0, 0)).columns, 1);
+    }
+
+    #[test]
+    fn tiles_and_captions_are_centered_in_rows() {
+        let options = GridOptions {
+            columns: 2,
+            tile_width: 4,
+            gap: (1, 1),
+        };
+        let tiles = [
+            tile(2, 2, '#', &["ab"]),
+            tile(4, 1, '@', &["long name"]),
+            tile(4, 1, '%', &[]),
+        ];
+        let grid = compose(&options, &tiles, |ch| ch);
+        assert_eq!(
+            lines(&grid),
+            [
+                " ##  @@@@",
+                " ##      ",
+                " ab  long",
+                "         ",
+                "%%%%     ",
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_tiles_and_grids_do_not_panic() {
+        let options = GridOptions {
+            columns: 3,
+            tile_width: 2,
+            gap: (1, 0),
+        };
+        let grid = compose(&options, &[tile(0, 0, '#', &["x"])], |ch| ch);
+        assert_eq!(lines(&grid), ["x "]);
+        let grid = compose(&options, &[], |ch: char| ch);
+        assert_eq!((grid.width(), grid.height()), (0, 0));
+    }
+}
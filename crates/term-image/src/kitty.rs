@@ -1,14 +1,21 @@
 // TODO: Revamp this and add more options.
 //       Probably extract it to a crate too
 
-use super::resize_image;
-use image::{DynamicImage, GenericImageView};
-use std::io::{self, Write};
+use super::{frame_duration, resize_image};
+use image::{DynamicImage, Frame, GenericImageView};
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 const PROTOCOL_START: &[u8] = b"\x1b_G";
 const PROTOCOL_END: &[u8] = b"\x1b\\";
 const MAX_BUFFER: usize = 2048;
 
+/// Counter for animation image ids, combined with the process id so separate runs don't replace
+/// each other's animations
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
 fn print_cmd_payload(out: &mut impl Write, cmds: &[(&str, &str)], payload: &str) -> io::Result<()> {
     let cmds = cmds
         .iter()
@@ -33,6 +40,20 @@ fn print_cmd_payload(out: &mut impl Write, cmds: &[(&str, &str)], payload: &str)
     Ok(())
 }
 
+fn print_cmd(out: &mut impl Write, cmds: &[(&str, &str)]) -> io::Result<()> {
+    let cmds = cmds
+        .iter()
+        .map(|(l, r)| format!("{}={}", l, r))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    out.write_all(PROTOCOL_START)?;
+    write!(out, "{}", cmds)?;
+    out.write_all(PROTOCOL_END)?;
+
+    Ok(())
+}
+
 /// Display an image from an canonical path
 ///
 /// # Notes
@@ -52,15 +73,26 @@ fn display_path(out: &mut impl Write, path: &str) -> io::Result<()> {
     Ok(())
 }
 
-fn display_image(out: &mut impl Write, img: &DynamicImage, max_size: (u16, u16)) -> io::Result<()> {
+/// Resize `img` and encode it as a base64 payload, returning the payload, bits per pixel, and size
+fn encode_image(img: &DynamicImage, max_size: (u16, u16)) -> (String, u8, u32, u32) {
     let img = resize_image(img, (1, 1), max_size);
     let (width, height) = img.dimensions();
     let (data, bits) = match img {
         DynamicImage::ImageRgb8(rgb) => (rgb.to_vec(), 24),
         DynamicImage::ImageRgba8(rgba) => (rgba.to_vec(), 32),
-        _ => unimplemented!("IMG Variant"),
+        // Kitty only takes 8 bit RGB(A), so grayscale and 16 bit images are converted
+        img => (img.to_rgba8().into_raw(), 32),
     };
-    let payload = base64::encode_config(&data, base64::STANDARD);
+    (
+        base64::encode_config(&data, base64::STANDARD),
+        bits,
+        width,
+        height,
+    )
+}
+
+fn display_image(out: &mut impl Write, img: &DynamicImage, max_size: (u16, u16)) -> io::Result<()> {
+    let (payload, bits, width, height) = encode_image(img, max_size);
 
     print_cmd_payload(
         out,
@@ -77,6 +109,85 @@ fn display_image(out: &mut impl Write, img: &DynamicImage, max_size: (u16, u16))
     Ok(())
 }
 
+/// Display an image scaled over `cells` (columns, rows), leaving the cursor where it was
+fn display_image_cells(
+    out: &mut impl Write,
+    img: &DynamicImage,
+    cells: (u32, u32),
+) -> io::Result<()> {
+    // Thumbnails don't need more pixels than a typical cell size
+    let max_size = (
+        (cells.0 * 16).min(u32::from(u16::MAX)) as u16,
+        (cells.1 * 32).min(u32::from(u16::MAX)) as u16,
+    );
+    let (payload, bits, width, height) = encode_image(img, max_size);
+
+    print_cmd_payload(
+        out,
+        &[
+            ("f", bits.to_string().as_str()),
+            ("a", "T"),
+            ("i", "0"),
+            ("t", "d"),
+            ("s", width.to_string().as_str()),
+            ("v", height.to_string().as_str()),
+            ("c", cells.0.to_string().as_str()),
+            ("r", cells.1.to_string().as_str()),
+            ("C", "1"),
+        ],
+        &payload,
+    )?;
+    Ok(())
+}
+
+/// Transmit every frame and let the terminal play the animation
+///
+/// `loops` is the number of times to play the animation, `None` for forever.
+fn display_animation(
+    out: &mut impl Write,
+    frames: impl IntoIterator<Item = Frame>,
+    max_size: (u16, u16),
+    loops: Option<u32>,
+) -> io::Result<()> {
+    let id = ((std::process::id() << 8) | (NEXT_ID.fetch_add(1, Ordering::Relaxed) & 0xff)).max(1);
+    let id = id.to_string();
+    let gap = |frame: &Frame| frame_duration(frame.delay()).as_millis().to_string();
+
+    for (i, frame) in frames.into_iter().enumerate() {
+        let gap = gap(&frame);
+        let (payload, bits, width, height) =
+            encode_image(&DynamicImage::ImageRgba8(frame.into_buffer()), max_size);
+        let bits = bits.to_string();
+        let (width, height) = (width.to_string(), height.to_string());
+        let format = [
+            ("f", bits.as_str()),
+            ("i", id.as_str()),
+            ("t", "d"),
+            ("s", width.as_str()),
+            ("v", height.as_str()),
+        ];
+
+        if i == 0 {
+            // The first frame is the image itself, its gap is set separately
+            print_cmd_payload(out, &[&[("a", "T")], &format[..]].concat(), &payload)?;
+            print_cmd(out, &[("a", "a"), ("i", &id), ("r", "1"), ("z", &gap)])?;
+        } else {
+            print_cmd_payload(
+                out,
+                &[&[("a", "f"), ("z", gap.as_str())], &format[..]].concat(),
+                &payload,
+            )?;
+        }
+    }
+
+    // Kitty counts loops from 2, with 1 meaning forever
+    let loops = loops.map_or(1, |loops| loops + 1).to_string();
+    print_cmd(out, &[("a", "a"), ("i", &id), ("s", "3"), ("v", &loops)])?;
+    out.flush()?;
+
+    Ok(())
+}
+
 // TODO: Find out why terminal isnt sending anything
 // fn read_term_response() -> io::Result<()> {
 //     let mut stdout = stdout().into_raw_mode()?;
@@ -117,8 +228,62 @@ impl Kitty {
         display_image(out, img, options.size)
     }
 
+    /// Render an image in kitty over `cells` (columns, rows) without moving the cursor
+    ///
+    /// Used to place images next to each other, like thumbnails in a grid.
+    pub fn img_cells(
+        img: &DynamicImage,
+        cells: (u32, u32),
+        out: &mut impl Write,
+    ) -> std::io::Result<()> {
+        display_image_cells(out, img, cells)
+    }
+
+    /// Remove every image placed on the screen
+    pub fn clear(out: &mut impl Write) -> std::io::Result<()> {
+        print_cmd(out, &[("a", "d")])
+    }
+
+    /// Render a full resolution animation in kitty, played by the terminal
+    ///
+    /// `loops` is the number of times to play the animation, `None` for forever.
+    pub fn animated(
+        options: &KittyOptions,
+        frames: impl IntoIterator<Item = Frame>,
+        loops: Option<u32>,
+        out: &mut impl Write,
+    ) -> std::io::Result<()> {
+        display_animation(out, frames, options.size, loops)
+    }
+
     /// Render full resolution image in kitty from a path
     pub fn path(path: &str, out: &mut impl Write) -> std::io::Result<()> {
         display_path(out, path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Luma, LumaA, Rgb, Rgba};
+
+    #[test]
+    fn every_color_type_is_encoded_as_8_bit() {
+        let images = [
+            DynamicImage::ImageLuma8(ImageBuffer::from_pixel(4, 2, Luma([128]))),
+            DynamicImage::ImageLumaA8(ImageBuffer::from_pixel(4, 2, LumaA([128, 255]))),
+            DynamicImage::ImageRgb16(ImageBuffer::from_pixel(4, 2, Rgb([0, 32768, 65535]))),
+            DynamicImage::ImageRgba16(ImageBuffer::from_pixel(
+                4,
+                2,
+                Rgba([0, 32768, 65535, 65535]),
+            )),
+        ];
+        for img in &images {
+            let (payload, bits, width, height) = encode_image(img, (4, 2));
+            let data = base64::decode(payload).unwrap();
+            assert_eq!(bits, 32);
+            assert_eq!(data.len(), width as usize * height as usize * 4);
+        }
+    }
+}
@@ -1,12 +1,23 @@
-use image::{imageops::FilterType, DynamicImage, Rgb as RgbPixel, Rgba};
+use image::{
+    imageops::FilterType, Delay, DynamicImage, Frame, GenericImageView, Rgb as RgbPixel, RgbImage,
+    Rgba,
+};
+use itertools::{IntoChunks, Itertools};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::time::Duration;
 
 pub mod ascii;
 pub mod block;
 pub mod braille;
+mod color;
+pub mod export;
+pub mod grid;
 #[cfg(feature = "iterm")]
 pub mod iterm;
 #[cfg(feature = "kitty")]
 pub mod kitty;
+pub mod raster;
 
 /// Returns the closest multiple of a base
 pub fn closest_mult(x: u32, base: u32) -> u32 {
@@ -26,6 +37,196 @@ pub fn resize_image(
     )
 }
 
+/// Returns the number of cells (columns, rows) an image should cover to fit within a max size
+///
+/// Terminal cells are assumed to be twice as tall as they are wide, so the result keeps the
+/// aspect ratio of the image regardless of how many pixels a renderer samples per cell.
+pub fn fit_cells(dimensions: (u32, u32), max_size: (u16, u16)) -> (u32, u32) {
+    let (width, height) = dimensions;
+    if width == 0 || height == 0 || max_size.0 == 0 || max_size.1 == 0 {
+        return (0, 0);
+    }
+
+    let max_width = f64::from(max_size.0);
+    let max_height = f64::from(max_size.1);
+    let scale = (max_width / f64::from(width)).min(2. * max_height / f64::from(height));
+
+    (
+        ((f64::from(width) * scale).round() as u32).clamp(1, u32::from(max_size.0)),
+        ((f64::from(height) * scale / 2.).round() as u32).clamp(1, u32::from(max_size.1)),
+    )
+}
+
+/// Prepares an image for a cell based renderer
+///
+/// The image is resized once so that it covers at most `max_size` cells of `cell_size` pixels each,
+/// then any transparency is flattened onto `background_color`.
+pub fn prepare_frame(
+    img: &DynamicImage,
+    cell_size: (u32, u32),
+    max_size: (u16, u16),
+    background_color: RgbPixel<u8>,
+) -> RgbImage {
+    let (columns, rows) = fit_cells(img.dimensions(), max_size);
+    let img = img.resize_exact(
+        columns * cell_size.0,
+        rows * cell_size.1,
+        FilterType::Nearest,
+    );
+    flatten(&img, background_color)
+}
+
+/// Flattens any transparency in an image onto `background_color`, without resizing it
+pub fn flatten(img: &DynamicImage, background_color: RgbPixel<u8>) -> RgbImage {
+    let mut out = RgbImage::new(img.width(), img.height());
+    for (x, y, p) in img.pixels() {
+        let p = premultiply(p, background_color);
+        out.put_pixel(x, y, RgbPixel([p[0], p[1], p[2]]));
+    }
+    out
+}
+
+/// A grid of rendered cells, stored row by row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellBuffer<C> {
+    width: usize,
+    cells: Vec<C>,
+}
+
+impl<C> CellBuffer<C> {
+    /// Create a buffer from cells stored row by row
+    ///
+    /// # Panics
+    /// Panics if the number of cells isn't a multiple of `width`
+    pub fn new(width: usize, cells: Vec<C>) -> CellBuffer<C> {
+        let height = cells.len().checked_div(width).unwrap_or(0);
+        assert_eq!(
+            width * height,
+            cells.len(),
+            "cells do not fill the last row"
+        );
+        CellBuffer { width, cells }
+    }
+
+    /// Width in cells
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height in cells
+    pub fn height(&self) -> usize {
+        self.cells.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    /// Returns the cell at (x, y), if it is in the buffer
+    pub fn get(&self, x: usize, y: usize) -> Option<&C> {
+        if x >= self.width {
+            return None;
+        }
+        self.cells.get(y * self.width + x)
+    }
+
+    /// Iterate over each row of cells
+    pub fn rows(&self) -> std::slice::Chunks<'_, C> {
+        self.cells.chunks(self.width.max(1))
+    }
+
+    /// All cells, row by row
+    pub fn cells(&self) -> &[C] {
+        &self.cells
+    }
+
+    /// Split the buffer into rows of cells, for the iterator based `img` and `animated` functions
+    pub(crate) fn into_chunks(self) -> IntoChunks<CellIter<C>> {
+        let width = self.width.max(1);
+        CellIter(self.cells.into_iter()).chunks(width)
+    }
+}
+
+/// Iterator over the cells of a rendered image, row by row
+pub struct CellIter<C>(std::vec::IntoIter<C>);
+
+impl<C> Iterator for CellIter<C> {
+    type Item = C;
+
+    fn next(&mut self) -> Option<C> {
+        self.0.next()
+    }
+}
+
+/// Render each cell of a grid, rows are rendered in parallel with the `rayon` feature
+pub(crate) fn render_cells<C: Send>(
+    width: u32,
+    height: u32,
+    render: impl Fn(u32, u32) -> C + Sync + Send,
+) -> CellBuffer<C> {
+    #[cfg(feature = "rayon")]
+    let cells = (0..height)
+        .into_par_iter()
+        .flat_map_iter(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| render(x, y))
+        .collect();
+    #[cfg(not(feature = "rayon"))]
+    let cells = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| render(x, y))
+        .collect();
+
+    CellBuffer::new(width as usize, cells)
+}
+
+/// Render the frames of an animation, keeping their order
+///
+/// Frames are rendered concurrently with the `rayon` feature.
+pub fn render_frames<C: Send>(
+    frames: Vec<Frame>,
+    render: impl Fn(&DynamicImage) -> CellBuffer<C> + Sync + Send,
+) -> Vec<(Delay, CellBuffer<C>)> {
+    let render = |frame: Frame| {
+        let delay = frame.delay();
+        let img = DynamicImage::ImageRgba8(frame.into_buffer());
+        (delay, render(&img))
+    };
+
+    #[cfg(feature = "rayon")]
+    return frames.into_par_iter().map(render).collect();
+    #[cfg(not(feature = "rayon"))]
+    return frames.into_iter().map(render).collect();
+}
+
+/// Lazily render the frames of an animation, `batch` frames at a time
+///
+/// Each batch is rendered with `render_frames`, so with the `rayon` feature the frames in a batch
+/// are rendered concurrently while the rest are not decoded yet.
+pub fn render_frames_batched<'a, C: Send + 'a>(
+    frames: impl Iterator<Item = Frame> + 'a,
+    batch: usize,
+    render: impl Fn(&DynamicImage) -> CellBuffer<C> + Sync + Send + 'a,
+) -> impl Iterator<Item = (Delay, CellBuffer<C>)> + 'a {
+    let mut frames = frames;
+    std::iter::from_fn(move || {
+        let frames = frames.by_ref().take(batch.max(1)).collect::<Vec<_>>();
+        if frames.is_empty() {
+            None
+        } else {
+            Some(render_frames(frames, &render))
+        }
+    })
+    .flatten()
+}
+
+/// How long a frame with `delay` should be shown for
+///
+/// Like browsers, delays of 10ms or less are treated as 100ms.
+pub fn frame_duration(delay: Delay) -> Duration {
+    let duration = Duration::from(delay);
+    if duration <= Duration::from_millis(10) {
+        Duration::from_millis(100)
+    } else {
+        duration
+    }
+}
+
 /// Represents a 24bit rgb color
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Rgb(pub (u8, u8, u8));
@@ -45,6 +246,15 @@ impl Rgb {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Ansi(pub u8);
 
+/// The character and colors of a rendered cell, for output other than a terminal
+pub trait StyledCell {
+    fn ch(&self) -> char;
+    /// The color of the character, `None` for the default color
+    fn fg(&self) -> Option<Rgb>;
+    /// The color behind the character, `None` for the default color
+    fn bg(&self) -> Option<Rgb>;
+}
+
 /// Perform alpha premuliplication on a Rgba pixel to remove the alpha
 fn premultiply(p: Rgba<u8>, bg: RgbPixel<u8>) -> Rgba<u8> {
     if p[3] == 255 {
@@ -61,3 +271,112 @@ fn premultiply(p: Rgba<u8>, bg: RgbPixel<u8>) -> Rgba<u8> {
 
     p
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ascii::{glyphs, Ascii, AsciiMode, AsciiOptions, Ramp},
+        block::{Block, BlockOptions, Charset, ColorFit, GlyphMatch},
+        braille::{Braille, BrailleOptions},
+    };
+    use image::{Frames, RgbaImage};
+
+    /// (image dimensions, max size in cells), one that fills the cells exactly and one that doesn't
+    const SIZES: [((u32, u32), (u16, u16)); 2] = [((40, 40), (10, 5)), ((37, 23), (10, 5))];
+
+    fn frame((width, height): (u32, u32)) -> Frame {
+        Frame::new(RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x * 7) as u8, (y * 11) as u8, ((x + y) * 3) as u8, 255])
+        }))
+    }
+
+    fn frames(dimensions: (u32, u32)) -> Frames<'static> {
+        Frames::new(Box::new(std::iter::once(Ok(frame(dimensions)))))
+    }
+
+    fn chunks_size<I: Iterator>(chunks: IntoChunks<I>) -> (usize, usize) {
+        let rows = chunks
+            .into_iter()
+            .map(|row| row.count())
+            .collect::<Vec<_>>();
+        (rows.first().copied().unwrap_or(0), rows.len())
+    }
+
+    /// Check that a still image, `render_frames`, and the animated iterator agree on the size
+    fn assert_same_size<C: Send, I: Iterator>(
+        buffer: impl Fn(&DynamicImage) -> CellBuffer<C> + Sync + Send,
+        animated: impl Fn(Frames<'static>) -> IntoChunks<I>,
+    ) {
+        for &(dimensions, max_size) in &SIZES {
+            let img = DynamicImage::ImageRgba8(frame(dimensions).into_buffer());
+            let still = buffer(&img);
+            let size = (still.width(), still.height());
+            let (width, height) = fit_cells(dimensions, max_size);
+            assert_eq!(size, (width as usize, height as usize));
+
+            let rendered = render_frames(vec![frame(dimensions)], &buffer);
+            let (_, rendered) = &rendered[0];
+            assert_eq!((rendered.width(), rendered.height()), size);
+            assert_eq!(chunks_size(animated(frames(dimensions))), size);
+        }
+    }
+
+    #[test]
+    fn block_animated_and_still_sizes_match() {
+        for &(_, size) in &SIZES {
+            let options = BlockOptions {
+                char_set: Charset::All,
+                blend: true,
+                blend_threshold: 10,
+                background_color: RgbPixel([0, 0, 0]),
+                size,
+                color_fit: ColorFit::Split,
+                glyph_match: GlyphMatch::Bitmap,
+            };
+            assert_same_size(
+                |img| Block::buffer(&options, img),
+                |frames| Block::animated(&options, frames).next().unwrap().1,
+            );
+        }
+    }
+
+    #[test]
+    fn braille_animated_and_still_sizes_match() {
+        for &(_, size) in &SIZES {
+            let options = BrailleOptions {
+                size,
+                background_color: RgbPixel([0, 0, 0]),
+                background: true,
+            };
+            assert_same_size(
+                |img| Braille::buffer(&options, img),
+                |frames| Braille::animated(&options, frames).next().unwrap().1,
+            );
+        }
+    }
+
+    #[test]
+    fn ascii_animated_and_still_sizes_match() {
+        let modes = [
+            AsciiMode::Brightness(Ramp::standard()),
+            AsciiMode::Structural(glyphs::ASCII[..].into()),
+        ];
+        for mode in &modes {
+            for &(_, size) in &SIZES {
+                let options = AsciiOptions {
+                    size,
+                    background_color: RgbPixel([0, 0, 0]),
+                    mode: mode.clone(),
+                    background: false,
+                    invert: false,
+                    monochrome: false,
+                };
+                assert_same_size(
+                    |img| Ascii::buffer(&options, img),
+                    |frames| Ascii::animated(&options, frames).next().unwrap().1,
+                );
+            }
+        }
+    }
+}
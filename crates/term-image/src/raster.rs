@@ -0,0 +1,281 @@
+//! Draw rendered cells back into images, to preview and measure renders without a terminal
+//!
+//! Block cells are drawn at 4x8 pixels from the charset bitmaps, and braille cells at 2x4 pixels
+//! from their dot patterns.  To compare a render against its source, resize the source to the size
+//! of the drawn image first.
+
+use crate::{
+    block::{self, bitmaps},
+    braille, CellBuffer, Rgb as TermRgb,
+};
+use image::{Rgb, RgbImage};
+
+/// Size of a block cell in pixels
+pub const BLOCK_CELL_SIZE: (u32, u32) = (4, 8);
+/// Size of a braille cell in pixels
+pub const BRAILLE_CELL_SIZE: (u32, u32) = (2, 4);
+
+/// Draw block cells using the bitmaps of their characters
+///
+/// Shade characters (`░▒▓█`) are drawn as a mix of the foreground and background colors, and
+/// characters without a bitmap are drawn in the background color.
+pub fn block(buffer: &CellBuffer<block::Cell>) -> RgbImage {
+    let (cell_width, cell_height) = BLOCK_CELL_SIZE;
+    let mut img = RgbImage::new(
+        buffer.width() as u32 * cell_width,
+        buffer.height() as u32 * cell_height,
+    );
+    for (y, row) in buffer.rows().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            let shade = match cell.ch {
+                '\u{2591}' => Some(0.25),
+                '\u{2592}' => Some(0.5),
+                '\u{2593}' => Some(0.75),
+                '\u{2588}' => Some(1.),
+                _ => None,
+            };
+            let bitmap = bitmaps::ALL
+                .iter()
+                .find(|(_, ch)| *ch == cell.ch)
+                .map_or(0, |(bitmap, _)| *bitmap);
+
+            for i in 0..cell_width * cell_height {
+                let color = match shade {
+                    Some(shade) => mix(cell.fg, cell.bg, shade),
+                    None if bitmap & (1 << (31 - i)) != 0 => pixel(cell.fg),
+                    None => pixel(cell.bg),
+                };
+                img.put_pixel(
+                    x as u32 * cell_width + i % cell_width,
+                    y as u32 * cell_height + i / cell_width,
+                    color,
+                );
+            }
+        }
+    }
+    img
+}
+
+/// Draw braille cells as their raised dots, with unset dots in `background` for cells without a
+/// background color
+pub fn braille(buffer: &CellBuffer<braille::Cell>, background: Rgb<u8>) -> RgbImage {
+    // The (x, y) of each dot, in the order of the bits of the character
+    const DOTS: [(u32, u32); 8] = [
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (1, 0),
+        (1, 1),
+        (1, 2),
+        (0, 3),
+        (1, 3),
+    ];
+
+    let (cell_width, cell_height) = BRAILLE_CELL_SIZE;
+    let mut img = RgbImage::new(
+        buffer.width() as u32 * cell_width,
+        buffer.height() as u32 * cell_height,
+    );
+    for (y, row) in buffer.rows().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            let bits = u32::from(cell.ch).wrapping_sub(0x2800);
+            let bits = if bits <= 0xff { bits } else { 0 };
+            let bg = cell.bg.map_or(background, pixel);
+            for (bit, (dot_x, dot_y)) in DOTS.iter().enumerate() {
+                let color = if bits & (1 << bit) != 0 {
+                    pixel(cell.fg)
+                } else {
+                    bg
+                };
+                img.put_pixel(
+                    x as u32 * cell_width + dot_x,
+                    y as u32 * cell_height + dot_y,
+                    color,
+                );
+            }
+        }
+    }
+    img
+}
+
+/// Peak signal-to-noise ratio between two images in decibels, higher is closer
+///
+/// Identical images are infinitely close.  Panics if the images differ in size.
+pub fn psnr(a: &RgbImage, b: &RgbImage) -> f64 {
+    assert_eq!(a.dimensions(), b.dimensions(), "images differ in size");
+    let squared_error = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&a, &b)| (f64::from(a) - f64::from(b)).powi(2))
+        .sum::<f64>();
+    let mse = squared_error / a.as_raw().len().max(1) as f64;
+    if mse == 0. {
+        f64::INFINITY
+    } else {
+        10. * (255. * 255. / mse).log10()
+    }
+}
+
+/// Mean structural similarity of the luma of two images, from -1 to 1 where 1 is identical
+///
+/// Compared in 8x8 windows, with smaller windows at the right and bottom edges, and each window
+/// weighted by its number of pixels.  Panics if the images differ in size.
+pub fn ssim(a: &RgbImage, b: &RgbImage) -> f64 {
+    const WINDOW: u32 = 8;
+    const C1: f64 = (0.01 * 255.) * (0.01 * 255.);
+    const C2: f64 = (0.03 * 255.) * (0.03 * 255.);
+
+    assert_eq!(a.dimensions(), b.dimensions(), "images differ in size");
+    let (width, height) = a.dimensions();
+    if width == 0 || height == 0 {
+        return 1.;
+    }
+
+    let mut total = 0.;
+    for top in (0..height).step_by(WINDOW as usize) {
+        for left in (0..width).step_by(WINDOW as usize) {
+            let bottom = (top + WINDOW).min(height);
+            let right = (left + WINDOW).min(width);
+            let pixels = (top..bottom)
+                .flat_map(|y| (left..right).map(move |x| (x, y)))
+                .map(|(x, y)| (luma(a.get_pixel(x, y)), luma(b.get_pixel(x, y))))
+                .collect::<Vec<_>>();
+
+            let n = pixels.len() as f64;
+            let mean_a = pixels.iter().map(|p| p.0).sum::<f64>() / n;
+            let mean_b = pixels.iter().map(|p| p.1).sum::<f64>() / n;
+            let var_a = pixels.iter().map(|p| (p.0 - mean_a).powi(2)).sum::<f64>() / n;
+            let var_b = pixels.iter().map(|p| (p.1 - mean_b).powi(2)).sum::<f64>() / n;
+            let covariance = pixels
+                .iter()
+                .map(|p| (p.0 - mean_a) * (p.1 - mean_b))
+                .sum::<f64>()
+                / n;
+
+            total += n * ((2. * mean_a * mean_b + C1) * (2. * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+        }
+    }
+    total / f64::from(width * height)
+}
+
+fn pixel(TermRgb((r, g, b)): TermRgb) -> Rgb<u8> {
+    Rgb([r, g, b])
+}
+
+/// `fg` covering `amount` of `bg`
+fn mix(fg: TermRgb, bg: TermRgb, amount: f64) -> Rgb<u8> {
+    let (fg, bg) = (pixel(fg), pixel(bg));
+    let mut mixed = bg;
+    for (mixed, (&fg, &bg)) in mixed.0.iter_mut().zip(fg.0.iter().zip(&bg.0)) {
+        *mixed = (f64::from(fg) * amount + f64::from(bg) * (1. - amount)).round() as u8;
+    }
+    mixed
+}
+
+fn luma(p: &Rgb<u8>) -> f64 {
+    0.299 * f64::from(p[0]) + 0.587 * f64::from(p[1]) + 0.114 * f64::from(p[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: TermRgb = TermRgb((255, 0, 0));
+    const BLUE: TermRgb = TermRgb((0, 0, 255));
+
+    #[test]
+    fn block_cells_are_drawn_from_their_bitmaps() {
+        let cells = vec![
+            block::Cell {
+                ch: '\u{2584}',
+                fg: RED,
+                bg: BLUE,
+            },
+            block::Cell {
+                ch: '\u{2592}',
+                fg: RED,
+                bg: BLUE,
+            },
+        ];
+        let img = block(&CellBuffer::new(2, cells));
+        assert_eq!(img.dimensions(), (8, 8));
+        for y in 0..8 {
+            for x in 0..4 {
+                // The lower half block
+                let expected = if y < 4 { pixel(BLUE) } else { pixel(RED) };
+                assert_eq!(*img.get_pixel(x, y), expected);
+                // The medium shade, mixed evenly
+                assert_eq!(*img.get_pixel(x + 4, y), Rgb([128, 0, 128]));
+            }
+        }
+    }
+
+    #[test]
+    fn braille_dots_are_drawn_in_unicode_order() {
+        let cells = vec![
+            // Dots 1 and 8
+            braille::Cell {
+                ch: '\u{2881}',
+                fg: RED,
+                bg: None,
+            },
+            // Dot 4
+            braille::Cell {
+                ch: '\u{2808}',
+                fg: RED,
+                bg: Some(BLUE),
+            },
+        ];
+        let background = Rgb([0, 255, 0]);
+        let img = braille(&CellBuffer::new(2, cells), background);
+        assert_eq!(img.dimensions(), (4, 4));
+        for y in 0..4 {
+            for x in 0..4 {
+                let expected = match (x, y) {
+                    (0, 0) | (1, 3) | (3, 0) => pixel(RED),
+                    (0..=1, _) => background,
+                    _ => pixel(BLUE),
+                };
+                assert_eq!(*img.get_pixel(x, y), expected, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    /// A gradient whose size isn't a multiple of the SSIM window
+    fn gradient() -> RgbImage {
+        RgbImage::from_fn(19, 13, |x, y| {
+            Rgb([(x * 13) as u8, (y * 19) as u8, ((x + y) * 8) as u8])
+        })
+    }
+
+    #[test]
+    fn identical_images_match_exactly() {
+        let img = gradient();
+        assert_eq!(psnr(&img, &img), f64::INFINITY);
+        assert!((ssim(&img, &img) - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn different_images_score_lower() {
+        let img = gradient();
+        let mut changed = img.clone();
+        // Only change the partial windows at the edges, so they have to be compared too
+        for y in 0..13 {
+            for x in 16..19 {
+                changed.put_pixel(x, y, Rgb([255, 255, 255]));
+            }
+        }
+        assert!(psnr(&img, &changed) < 30.);
+        assert!(ssim(&img, &changed) < 1.);
+
+        let noisy = RgbImage::from_fn(19, 13, |x, y| {
+            let p = img.get_pixel(x, y).0;
+            let offset = if (x + y) % 2 == 0 { 10 } else { 0 };
+            Rgb(p.map(|c| c.saturating_add(offset)))
+        });
+        assert!(psnr(&img, &noisy) > psnr(&img, &changed));
+        assert!(ssim(&img, &noisy) > ssim(&img, &changed));
+    }
+}